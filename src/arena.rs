@@ -0,0 +1,364 @@
+// Arena: several snakes on one board, each driven by its own controller.
+//
+// All living snakes move simultaneously. After the move a snake dies if its head
+// left the board, hit its own body, or hit any cell of another living snake
+// (head-to-head kills both). Dead snakes disappear from the board. The episode
+// ends when fewer than two snakes are alive (when the last one dies in solo play);
+// the survivor gets a win bonus.
+// The board is a plain rectangle: level maps, wrapping and typed food are not used here.
+
+use crate::food::*;
+use crate::game::{EndReason, GameConfig, StepOutcome};
+use crate::snake::*;
+use crate::utils::*;
+
+/// Reward for being the last snake alive.
+const WIN_REWARD: f32 = 1.0;
+
+/// Who steers a snake.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Controller {
+    Human, // arrow keys (window only)
+    Agent, // the shared DQN agent
+    Bot,   // scripted: greedy towards food, avoids immediate death
+}
+
+impl Controller {
+    /// Parse a comma-separated list, e.g. `human,agent,bot`.
+    pub fn parse_list(spec: &str) -> Result<Vec<Controller>, String> {
+        let mut out = Vec::new();
+        for part in spec.split(',').filter(|p| !p.is_empty()) {
+            out.push(match part {
+                "human" => Controller::Human,
+                "agent" => Controller::Agent,
+                "bot" => Controller::Bot,
+                _ => return Err(format!("unknown controller '{part}' (expected human, agent or bot)")),
+            });
+        }
+        if out.is_empty() {
+            return Err("arena needs at least one snake".to_string());
+        }
+        if out.iter().filter(|c| **c == Controller::Human).count() > 1 {
+            return Err("only one human snake is supported".to_string());
+        }
+        Ok(out)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Controller::Human => "human",
+            Controller::Agent => "agent",
+            Controller::Bot => "bot",
+        }
+    }
+}
+
+pub struct Arena {
+    w: usize,
+    h: usize,
+    snakes: Vec<Snake>,
+    alive: Vec<bool>,
+    end: Vec<Option<EndReason>>,
+    scores: Vec<u32>,
+    hunger: Vec<u32>,
+    hunger_limit: u32,
+    start_len: usize,
+    foods: Vec<Food>, // apples only
+    apples: usize,
+    rng: LcgRng,
+    episode_seed: u64,
+}
+
+impl Arena {
+    /// Arena with `n` snakes on the board described by `cfg` (size, start length,
+    /// hunger limit, seed, number of apples).
+    pub fn new(cfg: &GameConfig, n: usize) -> Self {
+        let (w, h) = (cfg.w, cfg.h);
+        // Snakes start on separate rows, so n is limited by the board height.
+        let n = n.clamp(1, h / 2);
+        let mut a = Self {
+            w,
+            h,
+            snakes: Vec::new(),
+            alive: vec![true; n],
+            end: vec![None; n],
+            scores: vec![0; n],
+            hunger: vec![0; n],
+            hunger_limit: cfg.hunger_limit,
+            start_len: cfg.start_len.clamp(1, w / 2 + 2),
+            foods: Vec::new(),
+            apples: cfg.apples.max(1),
+            rng: LcgRng::new(0),
+            episode_seed: cfg.seed,
+        };
+        a.reset_with_seed(cfg.seed);
+        a
+    }
+
+    /// Next episode with a seed derived from the previous one.
+    pub fn reset(&mut self) {
+        self.reset_with_seed(mix_seed(self.episode_seed));
+    }
+
+    /// Reset and play the next episode with exactly this seed.
+    pub fn reset_with_seed(&mut self, seed: u64) {
+        let n = self.alive.len();
+        self.episode_seed = seed;
+        self.rng = LcgRng::new(mix_seed(seed));
+        // Evenly spaced rows, all heads right of center facing right.
+        self.snakes = (0..n)
+            .map(|i| {
+                let y = (i + 1) * self.h / (n + 1);
+                Snake::new((self.w / 2) as i32, y as i32, self.start_len, self.w, self.h)
+            })
+            .collect();
+        self.alive = vec![true; n];
+        self.end = vec![None; n];
+        self.scores = vec![0; n];
+        self.hunger = vec![0; n];
+        self.foods.clear();
+        for _ in 0..self.apples {
+            self.spawn_apple();
+        }
+    }
+
+    /// Advance all living snakes by one relative action each (0 = left, 1 = straight,
+    /// 2 = right; entries of dead snakes are ignored). Returns one outcome per snake;
+    /// `done` is set for snakes that died in this step and for everyone once the episode ends.
+    pub fn step(&mut self, actions: &[u8]) -> Vec<StepOutcome> {
+        let n = self.snakes.len();
+        let mut out: Vec<StepOutcome> = (0..n).map(|_| StepOutcome { reward: 0.0, done: false }).collect();
+        let moving: Vec<bool> = self.alive.clone();
+
+        // Move everyone first, so collisions see the new positions of all snakes.
+        for i in (0..n).filter(|&i| moving[i]) {
+            let cur = self.snakes[i].dir();
+            let want = match actions.get(i).copied().unwrap_or(1) {
+                0 => cur.turn_left(),
+                2 => cur.turn_right(),
+                _ => cur,
+            };
+            self.snakes[i].apply_dir(want);
+            self.snakes[i].advance();
+            out[i].reward = -0.01;
+        }
+
+        // Collisions are judged on the board after the move, then dead snakes are removed.
+        let mut died: Vec<Option<EndReason>> = vec![None; n];
+        for i in (0..n).filter(|&i| moving[i]) {
+            let (hx, hy) = self.snakes[i].head();
+            died[i] = if hx < 0 || hy < 0 || hx >= self.w as i32 || hy >= self.h as i32 {
+                Some(EndReason::Wall)
+            } else if self.snakes[i].self_collision() {
+                Some(EndReason::SelfCollision)
+            } else if (0..n).any(|j| j != i && moving[j] && self.snakes[j].occupies(hx, hy)) {
+                Some(EndReason::Opponent)
+            } else {
+                None
+            };
+        }
+        for (i, o) in out.iter_mut().enumerate() {
+            if let Some(r) = died[i] {
+                self.kill(i, r);
+                *o = StepOutcome { reward: -1.0, done: true };
+            }
+        }
+
+        // Food and hunger for the survivors.
+        for (i, o) in out.iter_mut().enumerate() {
+            if !self.alive[i] {
+                continue;
+            }
+            let (hx, hy) = self.snakes[i].head();
+            if let Some(k) = self.foods.iter().position(|f| (f.x as i32, f.y as i32) == (hx, hy)) {
+                self.foods.swap_remove(k);
+                self.snakes[i].feed();
+                self.scores[i] += 1;
+                self.hunger[i] = 0;
+                o.reward += 1.0;
+                self.spawn_apple();
+            } else {
+                self.hunger[i] += 1;
+                if self.hunger[i] >= self.hunger_limit {
+                    self.kill(i, EndReason::Starvation);
+                    *o = StepOutcome { reward: o.reward - 0.2, done: true };
+                }
+            }
+        }
+
+        // Episode over: everyone is done, a lone survivor of a multi-snake game wins.
+        if self.is_over() {
+            let winner = self.winner();
+            for (i, o) in out.iter_mut().enumerate() {
+                o.done = true;
+                if Some(i) == winner {
+                    o.reward += WIN_REWARD;
+                }
+            }
+        }
+        out
+    }
+
+    /// Relative action for snake `i` that turns it towards `want` (see `Game::relative_action`).
+    pub fn relative_action(&self, i: usize, want: Dir) -> u8 {
+        let cur = self.snakes[i].dir();
+        if want == cur.turn_left() {
+            0
+        } else if want == cur.turn_right() {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Relative actions of snake `i` that do not run into an edge or a living body
+    /// (conservative: tails that are about to move count as blocked).
+    pub fn safe_actions(&self, i: usize) -> [bool; 3] {
+        let cur = self.snakes[i].dir();
+        let (hx, hy) = self.snakes[i].head();
+        [cur.turn_left(), cur, cur.turn_right()].map(|d| {
+            let (dx, dy) = d.delta();
+            !self.blocked(hx + dx, hy + dy)
+        })
+    }
+
+    /// Scripted bot: among the moves that do not die immediately, take the one closest
+    /// to the nearest apple (straight on ties); straight if every move is fatal.
+    pub fn bot_action(&self, i: usize) -> u8 {
+        let cur = self.snakes[i].dir();
+        let (hx, hy) = self.snakes[i].head();
+        let mut best: Option<(i32, u8)> = None;
+        for a in [1u8, 0, 2] {
+            let d = match a { 0 => cur.turn_left(), 2 => cur.turn_right(), _ => cur };
+            let (dx, dy) = d.delta();
+            let (x, y) = (hx + dx, hy + dy);
+            if self.blocked(x, y) {
+                continue;
+            }
+            let dist = self.nearest_apple(x, y).map_or(0, |(fx, fy)| (fx - x).abs() + (fy - y).abs());
+            if best.is_none_or(|(bd, _)| dist < bd) {
+                best = Some((dist, a));
+            }
+        }
+        best.map_or(1, |(_, a)| a)
+    }
+
+    /// Observation dimension: 5 rays × (wall/own body/food/opponent) + cos/sin to food + [length, hunger].
+    pub fn observation_dim(&self) -> usize { 5 * 4 + 2 + 2 }
+
+    /// Observation of snake `i`, built like `Game::observe` with an extra per-ray
+    /// channel for the nearest cell of any other living snake.
+    pub fn observe(&self, i: usize) -> Vec<f32> {
+        let mut obs = Vec::with_capacity(self.observation_dim());
+        let (hx, hy) = self.snakes[i].head();
+        let dir = self.snakes[i].dir();
+        let rays_local: [(i32, i32); 5] = [(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0)];
+        let max_r = self.w.max(self.h) as f32;
+
+        for (lx, ly) in rays_local {
+            let (dx, dy) = dir.rotate(lx, ly);
+            let mut dist_body = max_r;
+            let mut dist_food = max_r;
+            let mut dist_opp = max_r;
+            let (mut x, mut y) = (hx, hy);
+            let mut step = 0usize;
+            let dist_wall = loop {
+                step += 1;
+                x += dx;
+                y += dy;
+                if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+                    break step as f32;
+                }
+                if dist_body == max_r && self.snakes[i].occupies(x, y) {
+                    dist_body = step as f32;
+                }
+                if dist_food == max_r && self.foods.iter().any(|f| (f.x as i32, f.y as i32) == (x, y)) {
+                    dist_food = step as f32;
+                }
+                if dist_opp == max_r && self.opponent_at(i, x, y) {
+                    dist_opp = step as f32;
+                }
+            };
+            let norm = dist_wall.max(1.0);
+            for d in [dist_wall, dist_body, dist_food, dist_opp] {
+                let d = if d == max_r { norm } else { d };
+                obs.push((d / norm).min(1.0));
+            }
+        }
+
+        // Unit vector to the nearest apple in the head's local frame.
+        let (vx, vy) = self.nearest_apple(hx, hy).map_or((0, 0), |(fx, fy)| (fx - hx, fy - hy));
+        let (vx_l, vy_l) = dir.rotate(vx, vy);
+        let len = (((vx_l * vx_l + vy_l * vy_l) as f32).sqrt()).max(1e-6);
+        obs.push(vy_l as f32 / len);
+        obs.push(vx_l as f32 / len);
+
+        obs.push(self.snakes[i].len() as f32 / (self.w * self.h) as f32);
+        obs.push(self.hunger[i] as f32 / self.hunger_limit as f32);
+        obs
+    }
+
+    /// True when the episode has ended.
+    pub fn is_over(&self) -> bool {
+        let living = self.alive.iter().filter(|a| **a).count();
+        living == 0 || (self.alive.len() > 1 && living < 2)
+    }
+
+    /// The lone survivor of a multi-snake episode.
+    pub fn winner(&self) -> Option<usize> {
+        if self.alive.len() < 2 || self.alive.iter().filter(|a| **a).count() != 1 {
+            return None;
+        }
+        self.alive.iter().position(|a| *a)
+    }
+
+    // Remove snake `i` from the board.
+    fn kill(&mut self, i: usize, reason: EndReason) {
+        self.alive[i] = false;
+        self.end[i] = Some(reason);
+    }
+
+    // Cell that kills a head moving into it: off the board or any living body.
+    fn blocked(&self, x: i32, y: i32) -> bool {
+        x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32
+            || (0..self.snakes.len()).any(|j| self.alive[j] && self.snakes[j].occupies(x, y))
+    }
+
+    // Another living snake covers (x, y).
+    fn opponent_at(&self, i: usize, x: i32, y: i32) -> bool {
+        (0..self.snakes.len()).any(|j| j != i && self.alive[j] && self.snakes[j].occupies(x, y))
+    }
+
+    // Apple closest to (x, y) by Manhattan distance.
+    fn nearest_apple(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        self.foods.iter()
+            .map(|f| (f.x as i32, f.y as i32))
+            .min_by_key(|(fx, fy)| (fx - x).abs() + (fy - y).abs())
+    }
+
+    // Place an apple on a random free cell (nothing if the board is full).
+    fn spawn_apple(&mut self) {
+        let free: Vec<(usize, usize)> = (0..self.h)
+            .flat_map(|y| (0..self.w).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                !self.blocked(x as i32, y as i32) && !self.foods.iter().any(|f| (f.x, f.y) == (x, y))
+            })
+            .collect();
+        if free.is_empty() {
+            return;
+        }
+        let k = self.rng.gen_range_u32(free.len() as u32) as usize;
+        self.foods.push(Food::new(free[k].0, free[k].1, FoodKind::Apple, 0));
+    }
+
+    // -------- getters for rendering / logs --------
+    pub fn width(&self) -> usize { self.w }
+    pub fn height(&self) -> usize { self.h }
+    pub fn num_snakes(&self) -> usize { self.snakes.len() }
+    pub fn is_alive(&self, i: usize) -> bool { self.alive[i] }
+    pub fn score(&self, i: usize) -> u32 { self.scores[i] }
+    pub fn end_name(&self, i: usize) -> &'static str { self.end[i].map_or("-", |r| r.name()) }
+    pub fn episode_seed(&self) -> u64 { self.episode_seed }
+    pub fn foods(&self) -> &[Food] { &self.foods }
+    pub fn snake_segments(&self, i: usize) -> Vec<(i32, i32)> { self.snakes[i].segments_vec() }
+}
@@ -0,0 +1,138 @@
+// Curriculum over board size and starting length.
+//
+// Training starts on the first (easiest) stage. Every `window` training episodes on a
+// stage the agent plays `eval_episodes` greedy games (no exploration, no learning) on
+// fixed seeds and moves to the next stage when their mean score reaches the stage
+// threshold. The rolling training score includes exploratory moves and is only logged.
+// `Game::observe` is normalized by board size, so the same network carries over.
+
+use std::collections::VecDeque;
+use crate::encoder::FrameStack;
+use crate::game::{Game, GameConfig};
+use crate::network::Net;
+use crate::utils::{argmax, has_non_finite, mix_seed};
+
+/// One curriculum stage.
+pub struct Stage {
+    pub w: usize,
+    pub h: usize,
+    pub start_len: usize,
+    pub promote_at: f32, // mean greedy evaluation score needed to move on (ignored for the last stage)
+}
+
+pub struct Curriculum {
+    stages: Vec<Stage>,
+    cur: usize,
+    window: usize,
+    scores: VecDeque<u32>,
+    eval_episodes: usize,
+    since_eval: usize, // training episodes on this stage since the last evaluation
+}
+
+impl Curriculum {
+    pub fn new(stages: Vec<Stage>, window: usize, eval_episodes: usize) -> Self {
+        assert!(!stages.is_empty(), "curriculum needs at least one stage");
+        Self {
+            stages, cur: 0, window: window.max(1), scores: VecDeque::new(),
+            eval_episodes: eval_episodes.max(1), since_eval: 0,
+        }
+    }
+
+    /// Small boards first, ending on the regular 24×16 board.
+    pub fn default_stages() -> Vec<Stage> {
+        vec![
+            Stage { w: 8,  h: 8,  start_len: 3, promote_at: 4.0 },
+            Stage { w: 12, h: 10, start_len: 3, promote_at: 8.0 },
+            Stage { w: 16, h: 12, start_len: 3, promote_at: 12.0 },
+            Stage { w: 24, h: 16, start_len: 3, promote_at: f32::INFINITY },
+        ]
+    }
+
+    /// Parse stages from `WxH:len:threshold,...`, e.g. `8x8:3:4,24x16:3:0`.
+    pub fn parse_stages(spec: &str) -> Result<Vec<Stage>, String> {
+        let mut out = Vec::new();
+        for part in spec.split(',').filter(|p| !p.is_empty()) {
+            let fields: Vec<&str> = part.split(':').collect();
+            if fields.len() != 3 {
+                return Err(format!("bad stage '{part}' (expected WxH:len:threshold)"));
+            }
+            let (w, h) = fields[0].split_once('x').ok_or_else(|| format!("bad board size '{}'", fields[0]))?;
+            let w: usize = w.parse().map_err(|_| format!("bad width in '{part}'"))?;
+            let h: usize = h.parse().map_err(|_| format!("bad height in '{part}'"))?;
+            let start_len: usize = fields[1].parse().map_err(|_| format!("bad length in '{part}'"))?;
+            let promote_at: f32 = fields[2].parse().map_err(|_| format!("bad threshold in '{part}'"))?;
+            if w < 4 || h < 4 {
+                return Err(format!("board {w}x{h} too small in '{part}'"));
+            }
+            out.push(Stage { w, h, start_len, promote_at });
+        }
+        if out.is_empty() { return Err("empty curriculum".to_string()); }
+        Ok(out)
+    }
+
+    /// Index of the current stage.
+    pub fn stage(&self) -> usize { self.cur }
+
+    /// Game settings for the current stage: `base` with the stage's board size and length.
+    pub fn game_config(&self, base: &GameConfig) -> GameConfig {
+        let st = &self.stages[self.cur];
+        let mut cfg = base.clone();
+        cfg.w = st.w;
+        cfg.h = st.h;
+        cfg.start_len = st.start_len;
+        cfg
+    }
+
+    /// Rolling mean score over the window.
+    pub fn rolling_score(&self) -> f32 {
+        if self.scores.is_empty() { return 0.0; }
+        self.scores.iter().sum::<u32>() as f32 / self.scores.len() as f32
+    }
+
+    /// Record a training episode score; returns true when a greedy evaluation is due.
+    pub fn record(&mut self, score: u32) -> bool {
+        self.scores.push_back(score);
+        if self.scores.len() > self.window { self.scores.pop_front(); }
+        self.since_eval += 1;
+        self.cur + 1 < self.stages.len() && self.since_eval >= self.window
+    }
+
+    /// Mean score of `net` playing greedily on the current stage, over the same seeded
+    /// games at every evaluation. `frames` stacks the observations like in training.
+    pub fn evaluate(&self, net: &Net, frames: &FrameStack, base: &GameConfig) -> f32 {
+        let mut net = net.clone(); // the training net keeps its hidden state
+        let mut frames = frames.clone();
+        let mut game = Game::from_config(&self.game_config(base));
+        let mut total = 0.0f32;
+        for k in 0..self.eval_episodes {
+            game.reset_with_seed(mix_seed(base.seed ^ (k as u64 + 1)));
+            net.reset_state();
+            frames.reset();
+            // The hunger limit ends every episode.
+            loop {
+                let q = net.forward(&frames.observe(&game));
+                let a = if has_non_finite(&q) { 1 } else { argmax(&q) as u8 };
+                if game.step_ai(a).done { break; }
+            }
+            total += game.score() as f32;
+        }
+        total / self.eval_episodes as f32
+    }
+
+    /// Judge an evaluation score; returns true if it promoted to the next stage.
+    pub fn promote_on(&mut self, eval_score: f32) -> bool {
+        self.since_eval = 0;
+        if self.cur + 1 < self.stages.len() && eval_score >= self.stages[self.cur].promote_at {
+            self.cur += 1;
+            self.scores.clear(); // the rolling score follows the new stage
+            return true;
+        }
+        false
+    }
+
+    /// Short description of the current stage for logs.
+    pub fn describe(&self) -> String {
+        let st = &self.stages[self.cur];
+        format!("stage {}/{}: {}x{} len {}", self.cur + 1, self.stages.len(), st.w, st.h, st.start_len)
+    }
+}
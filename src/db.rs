@@ -1,19 +1,53 @@
 use std::fs::OpenOptions;                 // Файл с дозаписью.
-use std::io::Write;                       // Запись строк.
+use std::io::{BufRead, BufReader, Write}; // Чтение заголовка, запись строк.
+use crate::log;                           // Сообщаем о старом файле.
 
-pub fn append_episode_result(path: &str, ep: u64, ret: f32, steps: u64) -> Result<(), String> {
+// Колонки: ep, ret, steps, ts, stage, seed
+// (stage — номер стадии curriculum, 0 без него; seed — сид эпизода для Game::reset_with_seed).
+const HEADER: &str = "ep,ret,steps,ts,stage,seed";
+
+pub fn append_episode_result(path: &str, ep: u64, ret: f32, steps: u64, stage: usize, seed: u64) -> Result<(), String> {
+    // Файл другого формата (без заголовка — старые 4 колонки) не дописываем, а откладываем в сторону.
+    if let Some(first) = first_line(path) {
+        if first != HEADER {
+            let old = free_name(path);
+            std::fs::rename(path, &old).map_err(|e| format!("rename csv: {}", e))?;
+            log::warn(&format!("{path}: old column format, moved to {old}; starting a new file"));
+        }
+    }
     // Открываем/создаём CSV.
     let mut f = OpenOptions::new().create(true).append(true).open(path)
         .map_err(|e| format!("open csv: {}", e))?;
-    // Если файл только что создан — можно было бы написать заголовок; опустим для простоты.
+    // Новый (или пустой) файл начинаем с заголовка.
+    let mut line = String::new();
+    if f.metadata().map(|m| m.len()).unwrap_or(0) == 0 {
+        line.push_str(HEADER);
+        line.push('\n');
+    }
     // Пишем строку.
-    let line = format!("{},{},{},{}\n", ep, ret, steps, now_ts());
+    line.push_str(&format!("{},{},{},{},{},{}\n", ep, ret, steps, now_ts(), stage, seed));
     f.write_all(line.as_bytes()).map_err(|e| format!("write csv: {}", e))?;
     Ok(())
 }
 
+// Первая строка файла (None, если файла нет или он пуст).
+fn first_line(path: &str) -> Option<String> {
+    let f = std::fs::File::open(path).ok()?;
+    let mut line = String::new();
+    BufReader::new(f).read_line(&mut line).ok()?;
+    if line.is_empty() { None } else { Some(line.trim_end().to_string()) }
+}
+
+// Свободное имя для старого файла: results.csv -> results.1.csv, results.2.csv, ...
+fn free_name(path: &str) -> String {
+    let (stem, ext) = path.rsplit_once('.').unwrap_or((path, "csv"));
+    (1..).map(|n| format!("{stem}.{n}.{ext}"))
+        .find(|p| !std::path::Path::new(p).exists())
+        .unwrap()
+}
+
 // Временная метка в секундах (для CSV).
 fn now_ts() -> u64 {
     let now = std::time::SystemTime::now();
     now.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
-}
\ No newline at end of file
+}
@@ -0,0 +1,151 @@
+// Demonstration datasets: recorded (obs, action, reward, next_obs, done) steps.
+//
+// File layout (little endian):
+//   "SDEM" | u32 version | u32 obs_dim
+//   then records: obs[obs_dim] f32 | action u8 | reward f32 | done u8 | next_obs[obs_dim] f32
+// Files are append-only, so several sessions can be collected into one dataset.
+// Storing next_obs keeps every record a complete transition, even when an episode
+// is cut short (manual reset, closed window).
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"SDEM";
+const VERSION: u32 = 1;
+const HEADER_LEN: u64 = 12;
+
+/// One demonstrated step, with `action` in the relative 0/1/2 encoding of `step_ai`.
+pub struct DemoStep {
+    pub obs: Vec<f32>,
+    pub action: u8,
+    pub reward: f32,
+    pub next_obs: Vec<f32>,
+    pub done: bool,
+}
+
+/// Appends demonstration steps to a file.
+pub struct DemoWriter {
+    file: File,
+    obs_dim: usize,
+    pub steps: u64,   // records in the file (including earlier sessions)
+    pub dropped: u64, // bytes of a truncated last record cut off on open
+}
+
+impl DemoWriter {
+    /// Open `path` for appending; writes the header for a new file or checks it for an existing one.
+    /// A partial record at the end (a session killed mid-write) is cut off first, so new records
+    /// stay aligned; its size is reported in `dropped`.
+    pub fn open(path: &str, obs_dim: usize) -> std::io::Result<Self> {
+        let existing = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if existing > 0 {
+            let dim = read_header(&mut File::open(path)?)?;
+            if dim != obs_dim {
+                return Err(std::io::Error::other(format!("{path}: obs_dim {dim}, expected {obs_dim}")));
+            }
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut dropped = 0;
+        let steps = if existing > 0 {
+            let steps = (existing - HEADER_LEN) / record_len(obs_dim) as u64;
+            let complete = HEADER_LEN + steps * record_len(obs_dim) as u64;
+            if complete < existing {
+                dropped = existing - complete;
+                file.set_len(complete)?;
+            }
+            steps
+        } else {
+            let mut hdr = Vec::with_capacity(HEADER_LEN as usize);
+            hdr.extend_from_slice(MAGIC);
+            hdr.extend_from_slice(&VERSION.to_le_bytes());
+            hdr.extend_from_slice(&(obs_dim as u32).to_le_bytes());
+            file.write_all(&hdr)?;
+            0
+        };
+        Ok(Self { file, obs_dim, steps, dropped })
+    }
+
+    /// Append one step (written immediately, so nothing is lost if the window is closed).
+    pub fn push(&mut self, step: &DemoStep) -> std::io::Result<()> {
+        debug_assert_eq!(step.obs.len(), self.obs_dim);
+        debug_assert_eq!(step.next_obs.len(), self.obs_dim);
+        let mut buf = Vec::with_capacity(record_len(self.obs_dim));
+        for v in &step.obs { buf.extend_from_slice(&v.to_le_bytes()); }
+        buf.push(step.action);
+        buf.extend_from_slice(&step.reward.to_le_bytes());
+        buf.push(step.done as u8);
+        for v in &step.next_obs { buf.extend_from_slice(&v.to_le_bytes()); }
+        self.file.write_all(&buf)?;
+        self.steps += 1;
+        Ok(())
+    }
+}
+
+/// Load all steps of a demonstration file; `obs_dim` must match the file.
+pub fn load(path: &str, obs_dim: usize) -> std::io::Result<Vec<DemoStep>> {
+    let mut f = File::open(path)?;
+    let dim = read_header(&mut f)?;
+    if dim != obs_dim {
+        return Err(std::io::Error::other(format!("{path}: obs_dim {dim}, expected {obs_dim}")));
+    }
+    let mut data = Vec::new();
+    f.read_to_end(&mut data)?;
+
+    let rd_f32 = |o: &mut usize| -> f32 { let mut b = [0u8; 4]; b.copy_from_slice(&data[*o..*o + 4]); *o += 4; f32::from_le_bytes(b) };
+    let rec = record_len(dim);
+    let mut out = Vec::with_capacity(data.len() / rec);
+    let mut off = 0usize;
+    while off + rec <= data.len() { // a truncated tail record is ignored
+        let obs: Vec<f32> = (0..dim).map(|_| rd_f32(&mut off)).collect();
+        let action = data[off]; off += 1;
+        let reward = rd_f32(&mut off);
+        let done = data[off] != 0; off += 1;
+        let next_obs: Vec<f32> = (0..dim).map(|_| rd_f32(&mut off)).collect();
+        out.push(DemoStep { obs, action, reward, next_obs, done });
+    }
+    Ok(out)
+}
+
+// Size of one record in bytes.
+fn record_len(obs_dim: usize) -> usize { 2 * obs_dim * 4 + 1 + 4 + 1 }
+
+// Validate the header and return obs_dim.
+fn read_header(f: &mut File) -> std::io::Result<usize> {
+    let mut hdr = [0u8; HEADER_LEN as usize];
+    f.read_exact(&mut hdr)?;
+    if &hdr[0..4] != MAGIC { return Err(std::io::Error::other("bad demo header")); }
+    let ver = u32::from_le_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]);
+    if ver != VERSION { return Err(std::io::Error::other(format!("unsupported demo version {ver}"))); }
+    Ok(u32::from_le_bytes([hdr[8], hdr[9], hdr[10], hdr[11]]) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(v: f32) -> DemoStep {
+        DemoStep { obs: vec![v; 3], action: 2, reward: v, next_obs: vec![-v; 3], done: false }
+    }
+
+    #[test]
+    fn append_after_truncated_record() {
+        let path = std::env::temp_dir().join(format!("snake_demo_test_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let mut w = DemoWriter::open(path, 3).unwrap();
+        w.push(&step(1.0)).unwrap();
+        w.push(&step(2.0)).unwrap();
+        drop(w);
+        // A session killed in the middle of a record.
+        OpenOptions::new().append(true).open(path).unwrap().write_all(&[7u8; 9]).unwrap();
+
+        let mut w = DemoWriter::open(path, 3).unwrap();
+        assert_eq!((w.steps, w.dropped), (2, 9));
+        w.push(&step(3.0)).unwrap();
+        drop(w);
+        let steps = load(path, 3).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let rewards: Vec<f32> = steps.iter().map(|s| s.reward).collect();
+        assert_eq!(rewards, [1.0, 2.0, 3.0]);
+        assert_eq!((steps[2].obs.clone(), steps[2].action), (vec![3.0; 3], 2));
+    }
+}
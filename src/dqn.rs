@@ -5,60 +5,215 @@
 //   3) forward( s   ) на online — ПОСЛЕДНИЙ перед backward, чтобы градиент шёл по s.
 // Плюс: добавил лог q_abs_max для диагностики масштаба выходов.
 
-use crate::network::Net;     // Подключаем нашу MLP-сеть.
+use crate::network::{LayerSpec, Net}; // Наша сеть (последовательность слоёв из спецификации).
+use crate::demo::DemoStep;   // Демонстрации (люди/боты) для DQfD и BC.
+use crate::schedule::Schedule; // Расписания lr и ε по шагам среды.
+use crate::explore::{self, Exploration, Strategy}; // Стратегии исследования.
+use crate::optim::{self, OptimSpec, Optimizer}; // Оптимизаторы (SGD, RMSProp, Adam/AdamW).
 use crate::utils::*;         // RNG и числовые утилиты.
 use crate::log;              // Логгер (info/warn/error/scalar).
+use std::borrow::Cow;        // Наблюдение из реплея: ссылка или склеенные кадры.
+use std::collections::VecDeque; // Хранилище кадров реплея.
+use std::fmt;                // Вывод режима синхронизации таргета в лог.
 use std::fs::File;           // Файлы — для сохранения/загрузки состояния агента.
 use std::io::{Read, Write};  // Трейты чтения/записи байтов.
 
 // ---------------- Гиперпараметры агента ----------------
 
-/// Конфиг: размеры, буфер, батч, дисконт, шаги eps, софт-апдейт, прогрев, апдейты, сид.
+/// Конфиг: размеры, буфер, батч, дисконт, расписания lr и eps, софт-апдейт, прогрев, апдейты, сид.
 pub struct AgentConfig {
     pub obs_dim: usize,          // Размер наблюдения.
     pub act_dim: usize,          // Кол-во действий (3).
-    pub hidden: usize,           // Ширина скрытых слоёв.
     pub buffer_capacity: usize,  // Вместимость реплея.
     pub batch_size: usize,       // Размер минибатча.
     pub gamma: f32,              // Дисконт γ.
-    pub lr: f32,                 // Скорость обучения.
-    pub eps_start: f32,          // Стартовое ε.
-    pub eps_end: f32,            // Финальное ε.
-    pub eps_decay_steps: u64,    // За сколько шагов дойти до ε_end.
-    pub tau: f32,                // Коэф. софт-апдейта таргета.
+    pub lr: Schedule,            // Скорость обучения по шагам среды.
+    pub eps: Schedule,           // ε по шагам среды.
+    pub explore: Exploration,    // Стратегия исследования (ε-жадная, softmax, ε на эпизод; +safe).
+    pub optim: OptimSpec,        // Оптимизатор и его гиперпараметры.
+    pub target_update: TargetUpdate, // Синхронизация target-сети: софт (τ) или жёсткая копия раз в K шагов.
+    pub double_dqn: bool,        // Таргет Double-DQN (a* от online) или обычного DQN (max по target).
     pub learn_start: usize,      // Сколько транзиций накопить до обучения.
     pub updates_per_step: usize, // Сколько SGD-апдейтов на шаг среды.
     pub seed: u64,               // Сид RNG.
+    pub demo_margin: f32,        // Отступ l(a_E, a) в large-margin лоссе DQfD.
+    pub demo_lambda: f32,        // Вес large-margin лосса на демо-сэмплах.
+    pub weights_path: String,    // Файл весов online-сети (грузим в new, пишем в save_all).
+    pub state_path: String,      // Файл состояния агента (ε и шаги).
+    pub optim_path: String,      // Файл состояния оптимизатора (моменты и счётчик шагов).
+    pub obs_shape: (usize, usize, usize), // Форма наблюдения (c, h, w) — нужна conv-блокам.
+    pub arch: Vec<LayerSpec>,    // Скрытые слои сети (выходной Linear добавляется сам).
+    pub seq_len: usize,          // DRQN: длина обучающего куска эпизода (только для сети с GRU).
+    pub burn_in: usize,          // DRQN: сколько шагов перед куском только прогревают скрытое состояние.
+    pub frame_stack: usize,      // Кадров в наблюдении (1 — без стека); реплей хранит кадры по одному.
+    pub rails: Rails,            // Страховочные ремни обучения.
+}
+
+/// Синхронизация target-сети после каждого шага оптимизатора.
+///   soft:TAU   θ^- ← (1−τ)θ^- + τθ (Polyak)
+///   hard:K     θ^- ← θ раз в K шагов (как в DQN из Nature)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetUpdate {
+    Soft { tau: f32 },
+    Hard { every: u64 },
+}
+
+impl TargetUpdate {
+    /// Разбор `soft:TAU` (0 < τ ≤ 1) или `hard:K` (K > 0).
+    pub fn parse(spec: &str) -> Result<TargetUpdate, String> {
+        let bad = || format!("bad target update '{spec}' (expected soft:TAU with 0 < TAU <= 1 or hard:K with K > 0)");
+        match spec.split_once(':').ok_or_else(bad)? {
+            ("soft", v) => {
+                let tau: f32 = v.parse().map_err(|_| bad())?;
+                if !(tau > 0.0 && tau <= 1.0) { return Err(bad()); }
+                Ok(TargetUpdate::Soft { tau })
+            }
+            ("hard", v) => {
+                let every: u64 = v.parse().map_err(|_| bad())?;
+                if every == 0 { return Err(bad()); }
+                Ok(TargetUpdate::Hard { every })
+            }
+            _ => Err(bad()),
+        }
+    }
+}
+
+/// Обратное к `parse`.
+impl fmt::Display for TargetUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetUpdate::Soft { tau } => write!(f, "soft:{tau}"),
+            TargetUpdate::Hard { every } => write!(f, "hard:{every}"),
+        }
+    }
+}
+
+/// Страховочные ремни: клипы градиента, параметров, наград и таргетов и реакция на NaN.
+/// Бесконечный клип — ремень выключен. Декей весов — часть оптимизатора (`adamw:…:WD`).
+#[derive(Clone, Debug)]
+pub struct Rails {
+    pub max_grad_norm: f32,      // Глобальный клип нормы градиента.
+    pub param_clip: f32,         // Жёсткая обрезка параметров после шага.
+    pub reward_clip: f32,        // Клип наград в [-c, c].
+    pub target_clip: f32,        // Клип таргета y в [-c, c].
+    pub nan_halt: u64,           // После стольких NaN/Inf-событий — дамп и остановка (0 — никогда).
+    pub dump_prefix: String,     // Префикс файлов дампа (<prefix>_online.bin и т.д.).
+}
+
+impl Default for Rails {
+    fn default() -> Self {
+        Rails {
+            max_grad_norm: 1.0,
+            param_clip: 10.0,
+            reward_clip: 1.0,
+            target_clip: 10.0,
+            nan_halt: 0,
+            dump_prefix: "nan_dump".to_string(),
+        }
+    }
+}
+
+/// Сколько раз сработал каждый ремень (с запуска).
+#[derive(Clone, Debug, Default)]
+pub struct RailStats {
+    pub updates: u64,            // Сделанных шагов оптимизатора.
+    pub grads_clipped: u64,      // Шагов с обрезанной нормой градиента.
+    pub params_clamped: u64,     // Обрезанных значений параметров.
+    pub rewards_clipped: u64,    // Обрезанных наград.
+    pub targets_clipped: u64,    // Обрезанных таргетов.
+    pub samples_skipped: u64,    // Сэмплов, пропущенных из-за NaN/Inf в Q.
+    pub batches_skipped: u64,    // Батчей без шага (все сэмплы плохие или NaN в градиентах).
+    pub nan_events: u64,         // NaN/Inf-событий (пропуски батчей и NaN при выборе действия).
+}
+
+/// Наблюдение в реплее: вектор целиком или id кадров стека (старый → новый).
+enum Obs {
+    Full(Vec<f32>),
+    Frames(Vec<u64>),
 }
 
 /// Одна транзиция (s, a, r, s', done).
 struct Transition {
-    s: Vec<f32>,     // Состояние s.
+    s: Obs,          // Состояние s.
     a: u8,           // Действие a.
     r: f32,          // Награда r.
-    s2: Vec<f32>,    // Следующее состояние s'.
+    s2: Obs,         // Следующее состояние s'.
     done: bool,      // Флаг терминальности.
+    demo: bool,      // Транзиция из демонстрации (даёт large-margin лосс).
 }
 
-/// Кольцевой реплей-буфер.
+/// Кольцевой реплей-буфер. Первые `reserved` ячеек — демонстрации, их кольцо не перезаписывает.
+/// Со стеком кадров (`stack` > 1) каждый кадр хранится один раз, а транзиции держат его id:
+/// s' одной транзиции и s следующей делят k−1 кадр.
 struct ReplayBuffer {
     cap: usize,              // Вместимость.
     buf: Vec<Transition>,    // Данные.
     idx: usize,              // Куда писать при переполнении.
+    reserved: usize,         // Сколько ячеек в начале занято демо (DQfD держит их навсегда).
+    stack: usize,            // Кадров в наблюдении.
+    frames: VecDeque<Vec<f32>>, // Хранилище кадров (по возрастанию id).
+    base: u64,               // id кадра frames[0].
+    cur: Vec<u64>,           // id кадров последнего s' (пусто — начало эпизода).
 }
 impl ReplayBuffer {
-    fn new(capacity: usize) -> Self {            // Создаём буфер c заданной ёмкостью.
-        Self { cap: capacity, buf: Vec::with_capacity(capacity), idx: 0 }
+    fn new(capacity: usize, stack: usize) -> Self { // Создаём буфер c заданной ёмкостью.
+        Self {
+            cap: capacity, buf: Vec::with_capacity(capacity), idx: 0, reserved: 0,
+            stack: stack.max(1), frames: VecDeque::new(), base: 0, cur: Vec::new(),
+        }
     }
     fn len(&self) -> usize { self.buf.len() }    // Текущая длина.
     fn push(&mut self, tr: Transition) {         // Добавление (с перезаписью по кругу).
         if self.buf.len() < self.cap {
             self.buf.push(tr);
         } else {
+            if self.idx < self.reserved { self.idx = self.reserved; } // Демо-зону не трогаем.
             self.buf[self.idx] = tr;
-            self.idx = (self.idx + 1) % self.cap;
+            self.idx = self.reserved + (self.idx + 1 - self.reserved) % (self.cap - self.reserved);
+            // Самая старая транзиция теперь в idx: кадры до её первого больше не нужны.
+            if let Obs::Frames(ids) = &self.buf[self.idx].s {
+                while self.base < ids[0] {
+                    self.frames.pop_front();
+                    self.base += 1;
+                }
+            }
         }
     }
+    fn add_frame(&mut self, f: &[f32]) -> u64 {  // Новый кадр в хранилище, возвращает его id.
+        self.frames.push_back(f.to_vec());
+        self.base + self.frames.len() as u64 - 1
+    }
+    fn push_stacked(&mut self, s: &[f32], a: u8, r: f32, s2: &[f32], done: bool) { // Транзиция из склеенных наблюдений.
+        let fd = s.len() / self.stack;           // Длина одного кадра.
+        if self.cur.is_empty() {                 // Начало эпизода: кладём кадры s (повторы — один раз).
+            for j in 0..self.stack {
+                let f = &s[j * fd..(j + 1) * fd];
+                let id = match self.cur.last() {
+                    Some(&prev) if j > 0 && *f == s[(j - 1) * fd..j * fd] => prev,
+                    _ => self.add_frame(f),
+                };
+                self.cur.push(id);
+            }
+        }
+        let id2 = self.add_frame(&s2[s2.len() - fd..]); // В s' новый только последний кадр.
+        let s_ids = std::mem::take(&mut self.cur);
+        let s2_ids: Vec<u64> = s_ids[1..].iter().copied().chain([id2]).collect();
+        if !done { self.cur.clone_from(&s2_ids); }
+        self.push(Transition { s: Obs::Frames(s_ids), a, r, s2: Obs::Frames(s2_ids), done, demo: false });
+    }
+    fn obs<'a>(&'a self, o: &'a Obs) -> Cow<'a, [f32]> { // Наблюдение транзиции как вектор.
+        match o {
+            Obs::Full(v) => Cow::Borrowed(v),
+            Obs::Frames(ids) => Cow::Owned(
+                ids.iter().flat_map(|&id| self.frames[(id - self.base) as usize].iter().copied()).collect(),
+            ),
+        }
+    }
+    fn push_reserved(&mut self, tr: Transition) { // Демо-транзиция: только до собственных транзиций агента.
+        debug_assert_eq!(self.buf.len(), self.reserved);
+        self.buf.push(tr);
+        self.reserved += 1;
+    }
     fn sample_indices(&self, rng: &mut LcgRng, batch: usize) -> Vec<usize> { // Семплируем индексы.
         let n = self.buf.len() as u32;
         let mut out = Vec::with_capacity(batch);
@@ -67,6 +222,90 @@ impl ReplayBuffer {
     }
 }
 
+// ---------------- Реплей последовательностей (DRQN) ----------------
+
+/// Кусок эпизода: `burn` шагов прогрева + обучающие шаги.
+struct Sequence {
+    obs: Vec<Vec<f32>>, // Наблюдения: burn + len + 1 (последнее — s' последнего шага).
+    burn: usize,        // Сколько первых наблюдений только прогревают скрытое состояние.
+    actions: Vec<u8>,   // Действия обучающих шагов.
+    rewards: Vec<f32>,  // Награды обучающих шагов.
+    done: bool,         // Эпизод закончился на последнем шаге (там нет бутстрапа).
+}
+
+/// Кольцевой буфер кусков эпизодов + накопитель текущего эпизода.
+/// Эпизод режется на куски по `seq_len` шагов (хвост эпизода — короче), каждому куску
+/// достаются до `burn_in` предыдущих наблюдений для прогрева. Ёмкость — в транзициях.
+struct SequenceReplay {
+    cap: usize,              // Вместимость в транзициях.
+    buf: Vec<Sequence>,      // Куски.
+    idx: usize,              // Куда писать при переполнении.
+    transitions: usize,      // Сколько транзиций во всех кусках.
+    seq_len: usize,          // Длина куска.
+    burn_in: usize,          // Длина прогрева.
+    ep_obs: Vec<Vec<f32>>,   // Хвост текущего эпизода: наблюдения (на одно больше, чем действий).
+    ep_actions: Vec<u8>,     // Действия хвоста.
+    ep_rewards: Vec<f32>,    // Награды хвоста.
+    start: usize,            // Начало следующего куска в хвосте.
+}
+impl SequenceReplay {
+    fn new(capacity: usize, seq_len: usize, burn_in: usize) -> Self {
+        Self {
+            cap: capacity, buf: Vec::new(), idx: 0, transitions: 0, seq_len, burn_in,
+            ep_obs: Vec::new(), ep_actions: Vec::new(), ep_rewards: Vec::new(), start: 0,
+        }
+    }
+    fn len(&self) -> usize { self.transitions }  // Транзиций в буфере.
+    fn push_step(&mut self, s: &[f32], a: u8, r: f32, s2: &[f32], done: bool) {
+        if self.ep_obs.is_empty() { self.ep_obs.push(s.to_vec()); } // Первый шаг эпизода.
+        self.ep_actions.push(a);
+        self.ep_rewards.push(r);
+        self.ep_obs.push(s2.to_vec());
+        let end = self.ep_actions.len();
+        if end - self.start == self.seq_len || (done && end > self.start) {
+            self.emit(end, done);
+        }
+        if done {                                    // Новый эпизод начнётся с чистого хвоста.
+            self.ep_obs.clear();
+            self.ep_actions.clear();
+            self.ep_rewards.clear();
+            self.start = 0;
+        }
+    }
+    fn emit(&mut self, end: usize, done: bool) {     // Кусок [start, end) с прогревом перед ним.
+        let b0 = self.start.saturating_sub(self.burn_in);
+        let seq = Sequence {
+            obs: self.ep_obs[b0..=end].to_vec(),
+            burn: self.start - b0,
+            actions: self.ep_actions[self.start..end].to_vec(),
+            rewards: self.ep_rewards[self.start..end].to_vec(),
+            done,
+        };
+        self.transitions += seq.actions.len();
+        if self.transitions <= self.cap || self.buf.is_empty() {
+            self.buf.push(seq);
+        } else {                                     // Перезаписываем по кругу.
+            self.idx %= self.buf.len();
+            self.transitions -= self.buf[self.idx].actions.len();
+            self.buf[self.idx] = seq;
+            self.idx += 1;
+            // Длинный кусок на месте короткого: выкидываем следующие по старшинству, пока не влезем.
+            while self.transitions > self.cap && self.buf.len() > 1 {
+                let oldest = self.idx % self.buf.len();
+                self.transitions -= self.buf.remove(oldest).actions.len();
+                if oldest < self.idx { self.idx -= 1; } // Удалили из начала — новый кусок сдвинулся.
+            }
+        }
+        // Дальше нужны только последние burn_in наблюдений до нового начала.
+        self.start = end;
+        let keep = end.saturating_sub(self.burn_in);
+        self.ep_obs.drain(..keep);
+        self.ep_actions.drain(..keep);
+        self.ep_rewards.drain(..keep);
+        self.start -= keep;
+    }
+}
+
 // ---------------- Сам агент DQN/Double-DQN ----------------
 
 pub struct DQNAgent {
@@ -76,91 +315,209 @@ pub struct DQNAgent {
     pub target: Net,  // Замороженная сеть Q_{θ^-}.
 
     replay: ReplayBuffer, // Реплей-буфер.
+    seq_replay: Option<SequenceReplay>, // DRQN: реплей кусков эпизодов (если в сети есть GRU).
+    act_state: Vec<Vec<f32>>,           // DRQN: скрытое состояние, с которым агент действует.
 
+    optim: Box<dyn Optimizer>, // Оптимизатор online-сети (его состояние — в отдельном файле).
     rng: LcgRng,      // RNG для семплинга и ε-жадности.
     eps: f32,         // Текущее ε.
+    lr: f32,          // Текущая скорость обучения.
+    temp: f32,        // Текущая температура softmax-исследования.
     pub steps_done: u64, // Сколько шагов обучили — для расписаний.
+    updates: u64,        // Шагов обучения с синхронизацией таргета — для hard:K.
 
     pub last_loss: f32,  // Последний усреднённый лосс — для логов.
+    pub rail_stats: RailStats, // Счётчики срабатываний ремней.
+    halted: bool,        // Превышен лимит NaN — обучение остановлено, состояние сдамплено.
 }
 
-// ---------------- Страховочные константы ----------------
-
-const MAX_GRAD_NORM: f32 = 1.0;   // Глобальный клип нормы градиента.
-const WEIGHT_DECAY:   f32 = 1e-4; // AdamW-декей на весах.
-const PARAM_CLIP:     f32 = 10.0; // Жёсткая обрезка параметров после шага.
-const REWARD_CLIP:    f32 = 1.0;  // Клип наград в [-1, 1].
-const TARGET_CLIP:    f32 = 10.0; // Клип таргета y в [-10, 10].
-
 impl DQNAgent {
     /// Конструктор: создаём/инициализируем сети, буфер, RNG; пробуем загрузить веса/состояние.
-    pub fn new(cfg: AgentConfig) -> Self {
+    pub fn new(cfg: AgentConfig) -> Self { Self::build(cfg, true) }
+
+    /// Агент со свежими весами: чекпоинты из cfg не читаются (но `save_all` пишет в них).
+    pub fn fresh(cfg: AgentConfig) -> Self { Self::build(cfg, false) }
+
+    fn build(cfg: AgentConfig, resume: bool) -> Self {
         let seed            = cfg.seed;                     // Берём сид.
         let obs_dim         = cfg.obs_dim;                  // Размер входа.
         let act_dim         = cfg.act_dim;                  // Кол-во действий.
-        let hidden          = cfg.hidden;                   // Ширина скрытых слоёв.
         let buffer_capacity = cfg.buffer_capacity;          // Вместимость реплея.
 
-        let online = Net::new(obs_dim, hidden, hidden, act_dim, LcgRng::new(seed)); // Online-сеть.
-        let mut target = Net::new(obs_dim, hidden, hidden, act_dim, LcgRng::new(seed ^ 0xA5A5_5A5A)); // Target-сеть.
+        let shape = cfg.obs_shape;                          // Форма входа (c, h, w).
+        debug_assert_eq!(shape.0 * shape.1 * shape.2, obs_dim);
+        let online = Net::from_spec(shape, &cfg.arch, act_dim, LcgRng::new(seed)); // Online-сеть.
+        let mut target = Net::from_spec(shape, &cfg.arch, act_dim, LcgRng::new(seed ^ 0xA5A5_5A5A)); // Target-сеть.
         target.copy_from(&online);                          // Жёсткая копия online → target.
 
         let replay_rng_seed = 0xDEAD_BEEFu64 ^ seed;        // Сид для реплея.
+        let seq_replay = online.is_recurrent().then(|| {    // Рекуррентной сети — реплей последовательностей.
+            SequenceReplay::new(buffer_capacity, cfg.seq_len.max(1), cfg.burn_in)
+        });
+        let act_state = online.state();                     // Нулевое скрытое состояние.
+        let replay = ReplayBuffer::new(if seq_replay.is_some() { 0 } else { buffer_capacity }, cfg.frame_stack);
+        let optim = cfg.optim.build();                      // Оптимизатор с пустым состоянием.
 
         let mut ag = Self {                                 // Собираем структуру агента.
             cfg,
             online,
             target,
-            replay: ReplayBuffer::new(buffer_capacity),
+            replay,
+            seq_replay,
+            act_state,
+            optim,
             rng: LcgRng::new(replay_rng_seed),
             eps: 0.0,
+            lr: 0.0,
+            temp: 0.0,
             steps_done: 0,
+            updates: 0,
             last_loss: 0.0,
+            rail_stats: RailStats::default(),
+            halted: false,
         };
 
-        if ag.online.load("weights.bin").is_ok() {          // Пытаемся подгрузить веса.
-            ag.target.copy_from(&ag.online);                // Синхронизируем target.
-            log::info("loaded weights.bin");
+        if resume {
+            match ag.online.load(&ag.cfg.weights_path) {   // Пытаемся подгрузить веса.
+                Ok(()) => {
+                    ag.target.copy_from(&ag.online);        // Синхронизируем target.
+                    log::info(&format!("loaded {}", ag.cfg.weights_path));
+                    match optim::load_state(ag.optim.as_mut(), &ag.cfg.optim_path) { // Моменты — только к своим весам.
+                        Ok(()) => log::info(&format!("loaded {} (step {})", ag.cfg.optim_path, ag.optim.state().t)),
+                        // Чекпоинт с весами формата v1: моменты Adam жили в файле весов и отброшены.
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => log::warn(&format!(
+                            "no {}: Adam moments of v1 weights files are not loaded, the optimizer starts fresh",
+                            ag.cfg.optim_path,
+                        )),
+                        Err(e) => log::warn(&format!("optimizer state {} not loaded ({e}), starting it fresh", ag.cfg.optim_path)),
+                    }
+                }
+                // Нет файла — обычный первый запуск; битый файл — предупреждаем и начинаем с нуля.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::warn(&format!("{} not loaded ({e}), starting with fresh weights", ag.cfg.weights_path)),
+            }
         }
-        if let Ok((eps, steps)) = load_agent_state("agent_state.bin") { // Пытаемся подгрузить eps/steps.
+        let state = if resume { load_agent_state(&ag.cfg.state_path).ok() } else { None };
+        if let Some((eps, steps, updates)) = state {        // Пытаемся подгрузить eps/steps/updates.
             ag.eps = eps;
             ag.steps_done = steps;
-            log::info(&format!("loaded agent_state.bin (eps={:.3}, steps={})", eps, steps));
+            ag.updates = updates;
+            log::info(&format!("loaded {} (eps={:.3}, steps={}, updates={})", ag.cfg.state_path, eps, steps, updates));
         } else {
-            ag.eps = ag.cfg.eps_start;                      // Если нет состояния — стартуем с eps_start.
+            ag.eps = ag.cfg.eps.value(0);                   // Если нет состояния — стартуем с начала расписания.
+        }
+        ag.lr = ag.cfg.lr.value(ag.steps_done);             // lr — по расписанию с текущего шага.
+        if let Strategy::Softmax(t) = &ag.cfg.explore.strategy {
+            ag.temp = t.value(ag.steps_done);
+            ag.eps = 0.0;                                   // ε-случайности в softmax нет.
         }
+        ag.reset_episode();                                 // ε на эпизод — разыгрываем первое.
+        log::info(&format!("schedules: lr {} | eps {} | exploration {} | optim {}", ag.cfg.lr, ag.cfg.eps, ag.cfg.explore, ag.cfg.optim));
+        let r = &ag.cfg.rails;
+        log::info(&format!(
+            "rails: grad norm {} | param clip {} | reward clip {} | target clip {} | NaN halt {}",
+            r.max_grad_norm, r.param_clip, r.reward_clip, r.target_clip,
+            if r.nan_halt > 0 { r.nan_halt.to_string() } else { "off".to_string() },
+        ));
+        log::info(&format!(
+            "targets: {} DQN | target sync {}",
+            if ag.cfg.double_dqn { "double" } else { "vanilla" }, ag.cfg.target_update,
+        ));
         ag
     }
 
     /// Текущее ε.
     pub fn current_epsilon(&self) -> f32 { self.eps }
 
-    /// Длина реплея.
-    pub fn replay_len(&self) -> usize { self.replay.len() }
+    /// Длина реплея (в транзициях).
+    pub fn replay_len(&self) -> usize {
+        match &self.seq_replay {
+            Some(sr) => sr.len(),
+            None => self.replay.len(),
+        }
+    }
+
+    /// Начало нового эпизода: обнуляем скрытое состояние рекуррентной сети и
+    /// разыгрываем ε эпизода. `remember` с done делает это сам; нужно, если эпизод сброшен без него.
+    pub fn reset_episode(&mut self) {
+        self.replay.cur.clear();
+        if let Strategy::EpisodeEps { lo, hi } = self.cfg.explore.strategy {
+            self.eps = lo + self.rng.next_f32() * (hi - lo);
+        }
+        for h in &mut self.act_state {
+            for v in h.iter_mut() { *v = 0.0; }
+        }
+    }
 
-    /// ε-жадное действие по наблюдению.
-    pub fn select_action(&mut self, obs: &[f32]) -> u8 {
-        if self.rng.next_f32() < self.eps {                 // С вероятностью ε — случайное действие.
-            return self.rng.gen_range_u32(self.cfg.act_dim as u32) as u8;
+    /// Действие по наблюдению согласно стратегии исследования.
+    /// `safe[a]` — действие a не убивает змейку на следующем шаге (нужно для `+safe`).
+    pub fn select_action(&mut self, obs: &[f32], safe: &[bool]) -> u8 {
+        let allowed = if self.cfg.explore.safe { safe.to_vec() } else { vec![true; self.cfg.act_dim] };
+        let softmax = matches!(self.cfg.explore.strategy, Strategy::Softmax(_));
+        // Рекуррентная сеть видит каждый кадр, даже если действие будет случайным.
+        let q_rec = self.seq_replay.is_some().then(|| {
+            self.online.set_state(&self.act_state);
+            let q = self.online.forward(obs);
+            self.act_state = self.online.state();
+            q
+        });
+        if !softmax && self.rng.next_f32() < self.eps {     // С вероятностью ε — случайное действие.
+            return explore::random_action(&allowed, &mut self.rng) as u8;
         }
-        let q = self.online.forward(obs);                   // Иначе — forward и берём argmax.
+        let q = match q_rec {                               // Иначе — forward и берём argmax.
+            Some(q) => q,
+            None => self.online.forward(obs),
+        };
         if has_non_finite(&q) {                             // Защита от NaN/Inf.
-            log::error("Q contains NaN/Inf in select_action — fallback to random");
-            return self.rng.gen_range_u32(self.cfg.act_dim as u32) as u8;
+            self.note_nan("Q contains NaN/Inf in select_action — fallback to a random allowed action");
+            return explore::random_action(&allowed, &mut self.rng) as u8;
+        }
+        if softmax {                                        // Boltzmann: сэмплируем из softmax(Q/T).
+            return explore::softmax_action(&q, self.temp, &allowed, &mut self.rng) as u8;
         }
         argmax(&q) as u8                                    // Индекс максимального Q.
     }
 
     /// Кладём транзицию в реплей.
     pub fn remember(&mut self, s: &[f32], a: u8, r: f32, s2: &[f32], done: bool) {
-        self.replay.push(Transition { s: s.to_vec(), a, r, s2: s2.to_vec(), done });
+        if let Some(sr) = self.seq_replay.as_mut() {        // DRQN: копим эпизод кусками.
+            sr.push_step(s, a, r, s2, done);
+        } else if self.replay.stack > 1 {                   // Стек кадров: храним только новые кадры.
+            self.replay.push_stacked(s, a, r, s2, done);
+        } else {
+            self.replay.push(Transition { s: Obs::Full(s.to_vec()), a, r, s2: Obs::Full(s2.to_vec()), done, demo: false });
+        }
+        if done { self.reset_episode(); }                   // Новый эпизод.
+    }
+
+    /// DQfD: кладём демонстрации в начало реплея (до собственных транзиций), навсегда.
+    /// Занимают не больше половины буфера, чтобы агенту оставалось место.
+    pub fn preload_demonstrations(&mut self, demos: &[DemoStep]) {
+        if self.seq_replay.is_some() || self.replay.stack > 1 {
+            log::warn("preload_demonstrations: not supported for a recurrent net or stacked frames — skipping");
+            return;
+        }
+        if self.replay.len() > self.replay.reserved {
+            log::warn("preload_demonstrations: replay already has agent transitions — skipping");
+            return;
+        }
+        let room = self.cfg.buffer_capacity / 2;
+        if demos.len() > room {
+            log::warn(&format!("preload_demonstrations: {} demo steps, keeping first {}", demos.len(), room));
+        }
+        for d in demos.iter().take(room) {
+            self.replay.push_reserved(Transition {
+                s: Obs::Full(d.obs.clone()), a: d.action, r: d.reward, s2: Obs::Full(d.next_obs.clone()), done: d.done, demo: true,
+            });
+        }
+        log::info(&format!("preloaded {} demonstration transitions", self.replay.reserved));
     }
 
     /// Если реплей прогрелся — учимся (несколько апдейтов на шаг).
     pub fn maybe_learn(&mut self) {
-        if self.replay.len() < self.cfg.learn_start { return; } // Ждём прогрева.
+        if self.halted || self.replay_len() < self.cfg.learn_start { return; } // Остановлены или ждём прогрева.
         for _ in 0..self.cfg.updates_per_step {                  // Делаем N апдейтов.
-            self.learn_once();
+            if self.seq_replay.is_some() { self.learn_seq_once(); } else { self.learn_once(); }
         }
     }
 
@@ -172,31 +529,37 @@ impl DQNAgent {
         let mut loss_acc = 0.0f32;                          // Аккумулятор лосса (среднее по батчу).
         let mut td_errs: Vec<f32> = Vec::with_capacity(self.cfg.batch_size); // Для статистики TD-ошибок.
         let mut q_sel:   Vec<f32> = Vec::with_capacity(self.cfg.batch_size); // Для статистики Q выбранных действий.
+        let mut margin_acc = 0.0f32;                        // Large-margin лосс по демо-сэмплам.
+        let mut demo_n = 0usize;                            // Сколько демо-сэмплов попало в батч.
 
         for &k in &idxs {                                   // Итерируем по батчу индексов.
             let tr = &self.replay.buf[k];                   // Берём транзицию.
+            let (s, s2) = (self.replay.obs(&tr.s), self.replay.obs(&tr.s2)); // Её s и s' (со стеком — склеиваем кадры).
 
             // ---------- ВАЖНАЯ ЧАСТЬ: порядок вызовов forward ----------
 
-            // (1) Сначала считаем a* = argmax_a Q_online(s', a).
+            // (1) Double-DQN: сначала считаем a* = argmax_a Q_online(s', a).
             //     Это перетирает кеши online — и нам это сейчас безразлично.
-            let q_s2_online = self.online.forward(&tr.s2);
-            let a_star = argmax(&q_s2_online);
+            let a_star = self.cfg.double_dqn.then(|| argmax(&self.online.forward(&s2)));
 
-            // (2) Теперь строим таргет через TARGET-сеть: y = r + γ * Q_target(s', a*)
+            // (2) Теперь строим таргет через TARGET-сеть: y = r + γ * Q_target(s', a*),
+            //     в обычном DQN a* = argmax_a Q_target(s', a), т.е. y = r + γ * max_a Q_target(s', a).
             //     Кеши target независимы, они не мешают backward по online-сети.
-            let mut y = tr.r.clamp(-REWARD_CLIP, REWARD_CLIP);
+            let rails = &self.cfg.rails;
+            let mut y = clip_counted(tr.r, rails.reward_clip, &mut self.rail_stats.rewards_clipped);
             if !tr.done {
-                let q_s2_targ = self.target.forward(&tr.s2);
-                y += self.cfg.gamma * q_s2_targ[a_star];
+                let q_s2_targ = self.target.forward(&s2);
+                y += self.cfg.gamma * q_s2_targ[a_star.unwrap_or_else(|| argmax(&q_s2_targ))];
             }
-            let y = y.clamp(-TARGET_CLIP, TARGET_CLIP);
+            let y = clip_counted(y, rails.target_clip, &mut self.rail_stats.targets_clipped);
 
             // (3) И ТОЛЬКО ТЕПЕРЬ делаем forward по s на ONLINE-сети.
             //     Этот forward ДОЛЖЕН быть ПОСЛЕДНИМ перед backward,
-            //     чтобы кеши соответствовали вычислению Q(s,·).
-            let q_s = self.online.forward(&tr.s);
+            //     чтобы кеши соответствовали вычислению Q(s,·). Режим обучения — с dropout;
+            //     (1) и (2) выше — инференс, без шума.
+            let q_s = self.online.forward_train(&s);
             if has_non_finite(&q_s) {                       // На всякий случай — пропустим плохие сэмплы.
+                self.rail_stats.samples_skipped += 1;
                 continue;
             }
 
@@ -216,6 +579,25 @@ impl DQNAgent {
             let mut d_q = vec![0.0f32; self.cfg.act_dim];
             d_q[a] = g_scaled;
 
+            // DQfD large-margin: J_E = max_a [Q(s,a) + l(a_E,a)] − Q(s,a_E), l = margin при a ≠ a_E.
+            if tr.demo {
+                demo_n += 1;
+                let mut a_m = a;
+                let mut best = q_s[a];
+                for (b, &qb) in q_s.iter().enumerate() {
+                    if b != a && qb + self.cfg.demo_margin > best {
+                        best = qb + self.cfg.demo_margin;
+                        a_m = b;
+                    }
+                }
+                if a_m != a {
+                    let w = self.cfg.demo_lambda / (self.cfg.batch_size as f32);
+                    d_q[a_m] += w;
+                    d_q[a]   -= w;
+                    margin_acc += w * (best - q_s[a]);
+                }
+            }
+
             // Backward: накопим градиенты в слоях online-сети.
             self.online.backward_from_output_grad(d_q);
 
@@ -226,27 +608,16 @@ impl DQNAgent {
 
         // Если весь батч оказался «плохим» — пропускаем шаг.
         if td_errs.is_empty() {
-            log::warn("learn_once: batch had only bad/NaN samples — skipping update");
+            self.rail_stats.batches_skipped += 1;
+            self.note_nan("learn_once: batch had only bad/NaN samples — skipping update");
             return;
         }
 
-        // Проверка здоровья до шага оптимизатора.
-        let grad_l2 = self.online.grad_l2_sum_all().sqrt();         // Норма градиента (до клипа).
-        if self.online.non_finite_any() || !grad_l2.is_finite() {    // Если NaN/Inf — пропускаем шаг.
-            log::error(&format!("non-finite grads/params before step (||g||={}) — skip", grad_l2));
-            self.online.zero_grad();
-            return;
-        }
-
-        // Глобальный клип нормы и шаг AdamW.
-        let scale = self.online.clip_grad_norm(MAX_GRAD_NORM);       // Масштаб клипа (≤1).
-        self.online.step_adam(self.cfg.lr, 0.9, 0.999, 1e-8, scale, WEIGHT_DECAY); // Шаг оптимизатора.
-
-        // Жёсткий клип параметров (доп. ремень безопасности).
-        self.online.clamp_params(PARAM_CLIP);
+        // Проверка здоровья, глобальный клип нормы, шаг оптимизатора и клип параметров.
+        let Some(grad_l2) = self.guarded_step("learn_once") else { return; }; // Норма градиента (до клипа).
 
-        // Софт-апдейт таргет-сети: θ^- ← (1−τ)θ^- + τ θ.
-        self.target.soft_update_from(&self.online, self.cfg.tau);
+        // Синхронизация таргет-сети (софт или жёсткая).
+        self.sync_target();
 
         // Сохраняем усреднённый лосс по батчу.
         self.last_loss = loss_acc;
@@ -265,55 +636,351 @@ impl DQNAgent {
         log::scalar(self.steps_done, "q_sel_max",  qs.max);             // Макс Q выбранных действий.
         log::scalar(self.steps_done, "q_abs_max",  q_abs_max);          // Новый лог масштаба |Q|.
         log::scalar(self.steps_done, "epsilon",    self.eps);           // Текущее ε.
+        log::scalar(self.steps_done, "lr",         self.lr);            // Текущая скорость обучения.
+        if matches!(self.cfg.explore.strategy, Strategy::Softmax(_)) {
+            log::scalar(self.steps_done, "temperature", self.temp);     // Температура softmax.
+        }
+        if demo_n > 0 {
+            log::scalar(self.steps_done, "margin_loss", margin_acc);    // Large-margin лосс DQfD.
+            log::scalar(self.steps_done, "demo_frac", demo_n as f32 / idxs.len() as f32); // Доля демо в батче.
+        }
+    }
+
+    /// Один шаг обучения рекуррентной сети (DRQN) на кусках эпизодов.
+    /// Каждый кусок: прогрев скрытого состояния на burn-in (без градиента),
+    /// затем forward по обучающим шагам и backprop-through-time. Таргеты — как в learn_once
+    /// (Double-DQN или обычный DQN), Q(s') берём с того же прохода по куску (сети с нулевого состояния).
+    fn learn_seq_once(&mut self) {
+        let Some(sr) = self.seq_replay.as_ref() else { return; };
+        if sr.buf.is_empty() { return; }
+        let n_seq = (self.cfg.batch_size / self.cfg.seq_len.max(1)).max(1); // Кусков в батче.
+        let picks: Vec<usize> = (0..n_seq)
+            .map(|_| self.rng.gen_range_u32(sr.buf.len() as u32) as usize)
+            .collect();
+        let total: usize = picks.iter().map(|&k| sr.buf[k].actions.len()).sum(); // Обучающих шагов.
+
+        self.online.zero_grad();
+        let mut loss_acc = 0.0f32;
+        let mut td_errs: Vec<f32> = Vec::with_capacity(total);
+        let mut q_sel: Vec<f32> = Vec::with_capacity(total);
+
+        for &k in &picks {
+            let seq = &self.seq_replay.as_ref().unwrap().buf[k];
+            let (burn, len) = (seq.burn, seq.actions.len());
+
+            // (1) Инференс по всему куску: a* от online (Double-DQN) или от target, Q(s', a*) от target.
+            self.online.reset_state();
+            self.target.reset_state();
+            let mut ys = Vec::with_capacity(len);
+            let q_on: Option<Vec<Vec<f32>>> = self.cfg.double_dqn
+                .then(|| seq.obs.iter().map(|o| self.online.forward(o)).collect());
+            let q_tg: Vec<Vec<f32>> = seq.obs.iter().map(|o| self.target.forward(o)).collect();
+            for t in 0..len {
+                let rails = &self.cfg.rails;
+                let mut y = clip_counted(seq.rewards[t], rails.reward_clip, &mut self.rail_stats.rewards_clipped);
+                if !(seq.done && t + 1 == len) {            // Бутстрап везде, кроме конца эпизода.
+                    let j = burn + t + 1;
+                    let a_star = argmax(q_on.as_ref().map_or(&q_tg[j], |q| &q[j]));
+                    y += self.cfg.gamma * q_tg[j][a_star];
+                }
+                ys.push(clip_counted(y, rails.target_clip, &mut self.rail_stats.targets_clipped));
+            }
+
+            // (2) Прогрев состояния online-сети на burn-in, потом обучающий проход.
+            self.online.reset_state();
+            for o in &seq.obs[..burn] { self.online.forward(o); }
+            let q_s = self.online.forward_seq_train(&seq.obs[burn..burn + len]);
+            if q_s.iter().any(|q| has_non_finite(q)) {      // Плохой кусок — пропускаем.
+                self.rail_stats.samples_skipped += len as u64;
+                continue;
+            }
+
+            // (3) Huber-градиенты по выбранным действиям, усреднение по всем шагам батча.
+            let mut d_qs = Vec::with_capacity(len);
+            for t in 0..len {
+                let a = seq.actions[t] as usize;
+                let e = q_s[t][a] - ys[t];
+                td_errs.push(e);
+                q_sel.push(q_s[t][a]);
+                let g = if e.abs() <= 1.0 { e } else { e.signum() };
+                let mut d_q = vec![0.0f32; self.cfg.act_dim];
+                d_q[a] = g / total as f32;
+                d_qs.push(d_q);
+                let l = if e.abs() <= 1.0 { 0.5 * e * e } else { e.abs() - 0.5 };
+                loss_acc += l / total as f32;
+            }
+            self.online.backward_seq(d_qs);                 // BPTT по куску.
+        }
+
+        if td_errs.is_empty() {
+            self.rail_stats.batches_skipped += 1;
+            self.note_nan("learn_seq_once: batch had only bad/NaN sequences — skipping update");
+            return;
+        }
+
+        // Ремни, шаг оптимизатора и софт-апдейт — как в learn_once.
+        let Some(grad_l2) = self.guarded_step("learn_seq_once") else { return; };
+        self.sync_target();
+        self.last_loss = loss_acc;
+
+        let td = vec_stats(&td_errs);
+        let qs = vec_stats(&q_sel);
+        log::scalar(self.steps_done, "loss",       loss_acc);
+        log::scalar(self.steps_done, "grad_norm",  grad_l2);
+        log::scalar(self.steps_done, "td_mean",    td.mean);
+        log::scalar(self.steps_done, "td_min",     td.min);
+        log::scalar(self.steps_done, "td_max",     td.max);
+        log::scalar(self.steps_done, "q_sel_mean", qs.mean);
+        log::scalar(self.steps_done, "q_sel_min",  qs.min);
+        log::scalar(self.steps_done, "q_sel_max",  qs.max);
+        log::scalar(self.steps_done, "epsilon",    self.eps);
+        log::scalar(self.steps_done, "lr",         self.lr);
+        if matches!(self.cfg.explore.strategy, Strategy::Softmax(_)) {
+            log::scalar(self.steps_done, "temperature", self.temp);
+        }
+        log::scalar(self.steps_done, "seq_batch",  picks.len() as f32); // Кусков в батче.
     }
 
-    /// Обновляем расписание ε по номеру шага.
+    /// Супервизорный шаг: подтягиваем Q(s,·) online-сети к внешним таргетам
+    /// (например, к Q-оценкам корня MCTS). Действия с таргетом None (поиск их не посещал)
+    /// градиента не получают. Возвращает средний MSE по батчу.
+    pub fn fit_q_targets(&mut self, batch: &[(Vec<f32>, Vec<Option<f32>>)]) -> f32 {
+        if batch.is_empty() { return 0.0; }                 // Нечего учить.
+        self.online.zero_grad();                            // Сбрасываем градиенты.
+        let n = batch.len() as f32;
+        let mut loss_acc = 0.0f32;
+
+        for (s, y) in batch {
+            let q = self.online.forward_train(s);           // forward прямо перед backward.
+            if has_non_finite(&q) {                         // Плохой сэмпл — пропускаем.
+                self.rail_stats.samples_skipped += 1;
+                continue;
+            }
+            let mut d_q = vec![0.0f32; self.cfg.act_dim];
+            for (a, ya) in y.iter().enumerate() {
+                let Some(ya) = *ya else { continue; };      // Неизвестный таргет — не трогаем.
+                let ya = clip_counted(ya, self.cfg.rails.target_clip, &mut self.rail_stats.targets_clipped);
+                let e = q[a] - ya;                          // Ошибка по действию.
+                d_q[a] = e / n;                             // dL/dQ для 0.5·e², усреднено по батчу.
+                loss_acc += 0.5 * e * e / n;
+            }
+            self.online.backward_from_output_grad(d_q);
+        }
+
+        if self.guarded_step("fit_q_targets").is_none() { // Те же ремни, что и в learn_once.
+            return loss_acc;
+        }
+        self.sync_target();
+
+        self.last_loss = loss_acc;
+        log::scalar(self.steps_done, "fit_loss", loss_acc);
+        loss_acc
+    }
+
+    /// Behaviour cloning: кросс-энтропия softmax(Q(s,·)) против действий демонстратора.
+    /// Возвращает (средний лосс, точность argmax) по использованным сэмплам батча.
+    pub fn fit_bc(&mut self, batch: &[&DemoStep]) -> (f32, f32) {
+        if batch.is_empty() { return (0.0, 0.0); }
+        self.online.zero_grad();
+        let n = batch.len() as f32;
+        let mut loss_sum = 0.0f32;
+        let (mut hits, mut used) = (0usize, 0usize);
+
+        for d in batch {
+            let q = self.online.forward_train(&d.obs);
+            if has_non_finite(&q) {
+                self.rail_stats.samples_skipped += 1;
+                continue;
+            }
+            used += 1;
+            let a = d.action as usize;
+            if argmax(&q) == a { hits += 1; }
+            // Стабильный softmax: вычитаем максимум.
+            let m = q.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let exps: Vec<f32> = q.iter().map(|&v| (v - m).exp()).collect();
+            let z: f32 = exps.iter().sum();
+            // dL/dlogit = p − onehot(a_E).
+            let mut d_q: Vec<f32> = exps.iter().map(|&e| e / z / n).collect();
+            d_q[a] -= 1.0 / n;
+            loss_sum += -(exps[a] / z).max(1e-12).ln();
+            self.online.backward_from_output_grad(d_q);
+        }
+        // Лосс и точность — по сэмплам, которые реально пошли в батч (без пропущенных NaN).
+        let k = used.max(1) as f32;
+        let stats = (loss_sum / k, hits as f32 / k);
+
+        if self.guarded_step("fit_bc").is_none() {
+            return stats;
+        }
+        self.target.copy_from(&self.online);                // Вне RL-цикла таргет просто копия.
+        stats
+    }
+
+    /// Общие ремни шага: проверка здоровья, глобальный клип нормы, шаг оптимизатора, клип параметров.
+    /// Возвращает норму градиента до клипа или None, если из-за NaN/Inf шаг пропущен.
+    fn guarded_step(&mut self, what: &str) -> Option<f32> {
+        let grad_l2 = self.online.grad_l2_sum_all().sqrt();
+        if self.online.non_finite_any() || !grad_l2.is_finite() {    // Если NaN/Inf — пропускаем шаг.
+            self.online.zero_grad();
+            self.rail_stats.batches_skipped += 1;
+            self.note_nan(&format!("{what}: non-finite grads/params before step (||g||={grad_l2}) — skip"));
+            return None;
+        }
+        let scale = self.online.clip_grad_norm(self.cfg.rails.max_grad_norm); // Масштаб клипа (≤1).
+        if scale < 1.0 { self.rail_stats.grads_clipped += 1; }
+        self.online.step(self.optim.as_mut(), self.lr, scale);
+        self.rail_stats.params_clamped += self.online.clamp_params(self.cfg.rails.param_clip) as u64;
+        self.rail_stats.updates += 1;
+        Some(grad_l2)
+    }
+
+    /// Синхронизация таргет-сети после шага: софт-апдейт или жёсткая копия раз в K шагов.
+    fn sync_target(&mut self) {
+        self.updates += 1;
+        match self.cfg.target_update {
+            TargetUpdate::Soft { tau } => self.target.soft_update_from(&self.online, tau),
+            TargetUpdate::Hard { every } => {
+                // Свой счётчик, а не t оптимизатора: t сбрасывается при смене --optim и растёт
+                // при предобучении. Счётчик сохраняется в файле состояния агента.
+                if self.updates.is_multiple_of(every) {
+                    self.target.copy_from(&self.online);
+                }
+            }
+        }
+    }
+
+    /// NaN/Inf-событие: логируем, считаем; сверх лимита — дамп состояния и остановка обучения.
+    fn note_nan(&mut self, msg: &str) {
+        log::error(msg);
+        self.rail_stats.nan_events += 1;
+        let limit = self.cfg.rails.nan_halt;
+        if limit == 0 || self.halted || self.rail_stats.nan_events <= limit { return; }
+        self.halted = true;
+        log::error(&format!("{} NaN/Inf events (limit {limit}) — halting and dumping state", self.rail_stats.nan_events));
+        self.log_rails();
+        self.dump_state();
+    }
+
+    /// Обучение остановлено из-за NaN (вызывающий цикл должен завершиться).
+    pub fn halted(&self) -> bool { self.halted }
+
+    /// Дамп для разбора NaN: обе сети, оптимизатор и ε/шаги под префиксом `rails.dump_prefix`.
+    /// Рабочие чекпоинты не трогаем — в них последнее здоровое состояние.
+    fn dump_state(&self) {
+        let p = &self.cfg.rails.dump_prefix;
+        let results = [
+            (format!("{p}_online.bin"), self.online.save(&format!("{p}_online.bin"))),
+            (format!("{p}_target.bin"), self.target.save(&format!("{p}_target.bin"))),
+            (format!("{p}_optim.bin"), optim::save_state(self.optim.as_ref(), &format!("{p}_optim.bin"))),
+            (format!("{p}_agent.bin"), save_agent_state(&format!("{p}_agent.bin"), self.eps, self.steps_done, self.updates)),
+        ];
+        for (path, res) in results {
+            match res {
+                Ok(()) => log::info(&format!("dumped {path}")),
+                Err(e) => log::error(&format!("cannot dump {path}: {e}")),
+            }
+        }
+    }
+
+    /// Счётчики ремней в лог (вместе с периодическим сохранением).
+    pub fn log_rails(&self) {
+        let r = &self.rail_stats;
+        log::info(&format!(
+            "rails: updates {} | grads clipped {} | params clamped {} | rewards clipped {} | targets clipped {} | samples skipped {} | batches skipped {} | NaN {}",
+            r.updates, r.grads_clipped, r.params_clamped, r.rewards_clipped, r.targets_clipped,
+            r.samples_skipped, r.batches_skipped, r.nan_events,
+        ));
+    }
+
+    /// Обновляем ε, температуру и lr по расписаниям для номера шага.
     pub fn on_step(&mut self, global_steps: u64) {
-        self.steps_done = global_steps;                                        // Обновляем счётчик шагов.
-        let t = (self.steps_done as f32 / self.cfg.eps_decay_steps as f32).min(1.0); // Нормируем 0..1.
-        self.eps = self.cfg.eps_start + t * (self.cfg.eps_end - self.cfg.eps_start); // Линейный спуск ε.
+        self.steps_done = global_steps;                  // Обновляем счётчик шагов.
+        match &self.cfg.explore.strategy {
+            Strategy::EpisodeEps { .. } => {}            // ε разыгран на эпизод.
+            Strategy::Softmax(t) => self.temp = t.value(global_steps), // ε остаётся 0.
+            Strategy::EpsGreedy => self.eps = self.cfg.eps.value(global_steps), // ε по расписанию.
+        }
+        self.lr = self.cfg.lr.value(global_steps);       // lr по расписанию.
     }
 
-    /// Сохранение весов и состояния агента на диск.
+    /// Сохранение весов и состояния агента на диск (и отчёт о ремнях в лог).
+    /// После остановки по NaN не пишем — иначе затрём здоровый чекпоинт.
     pub fn save_all(&self) {
-        if self.online.save("weights.bin").is_ok() {           // Пишем веса online-сети.
-            log::info("saved weights.bin");
+        self.log_rails();
+        if self.halted { return; }
+        if self.online.save(&self.cfg.weights_path).is_ok() {  // Пишем веса online-сети.
+            log::info(&format!("saved {}", self.cfg.weights_path));
         }
-        if save_agent_state("agent_state.bin", self.eps, self.steps_done).is_ok() { // Пишем ε и шаги.
-            log::info("saved agent_state.bin");
+        if save_agent_state(&self.cfg.state_path, self.eps, self.steps_done, self.updates).is_ok() { // Пишем ε и счётчики.
+            log::info(&format!("saved {}", self.cfg.state_path));
+        }
+        if optim::save_state(self.optim.as_ref(), &self.cfg.optim_path).is_ok() { // Пишем состояние оптимизатора.
+            log::info(&format!("saved {}", self.cfg.optim_path));
         }
     }
 }
 
 // ---------------- Вспомогательные функции ----------------
 
-/// Индекс максимума.
-fn argmax(v: &[f32]) -> usize {
-    let mut best_i = 0;                 // Текущий лучший индекс.
-    let mut best_v = v[0];              // Текущее лучшее значение.
-    for i in 1..v.len() {               // Проходим массив.
-        if v[i] > best_v {              // Нашли больше — обновляем.
-            best_v = v[i];
-            best_i = i;
-        }
-    }
-    best_i                               // Возвращаем индекс.
+/// Клип v в [-c, c] со счётчиком срабатываний.
+fn clip_counted(v: f32, c: f32, hits: &mut u64) -> f32 {
+    if v.abs() > c { *hits += 1; }
+    v.clamp(-c, c)
 }
 
-/// Сохраняем ε и steps в бинарный файл.
-fn save_agent_state(path: &str, eps: f32, steps_done: u64) -> std::io::Result<()> {
+/// Сохраняем ε, steps и updates в бинарный файл.
+fn save_agent_state(path: &str, eps: f32, steps_done: u64, updates: u64) -> std::io::Result<()> {
     let mut f = File::create(path)?;                  // Создаём/переписываем файл.
     f.write_all(&eps.to_le_bytes())?;                 // Пишем 4 байта f32 в LE.
     f.write_all(&steps_done.to_le_bytes())?;          // Пишем 8 байт u64 в LE.
+    f.write_all(&updates.to_le_bytes())?;             // И ещё 8 байт u64 — шаги обучения.
     Ok(())                                            // Ок.
 }
 
-/// Загружаем ε и steps из бинарного файла.
-fn load_agent_state(path: &str) -> std::io::Result<(f32, u64)> {
-    let mut f = File::open(path)?;                    // Открываем файл.
-    let mut buf = [0u8; 12];                          // 4 + 8 байт.
-    f.read_exact(&mut buf)?;                          // Читаем строго 12 байт.
+/// Загружаем ε, steps и updates из бинарного файла.
+/// В старых файлах (12 байт) счётчика updates нет — считаем его нулём.
+fn load_agent_state(path: &str) -> std::io::Result<(f32, u64, u64)> {
+    let mut buf = Vec::new();
+    File::open(path)?.read_to_end(&mut buf)?;         // Читаем файл целиком.
+    if buf.len() != 12 && buf.len() != 20 {           // 4 + 8 (+ 8) байт.
+        return Err(std::io::Error::other(format!("{path}: {} bytes, expected 12 or 20", buf.len())));
+    }
     let mut fe = [0u8; 4]; fe.copy_from_slice(&buf[0..4]);   // Первые 4 — f32 ε.
     let mut fs = [0u8; 8]; fs.copy_from_slice(&buf[4..12]);  // Следующие 8 — u64 steps.
-    Ok((f32::from_le_bytes(fe), u64::from_le_bytes(fs)))     // Возвращаем распакованные значения.
+    let mut fu = [0u8; 8];
+    if buf.len() == 20 { fu.copy_from_slice(&buf[12..20]); } // Последние 8 — u64 updates.
+    Ok((f32::from_le_bytes(fe), u64::from_le_bytes(fs), u64::from_le_bytes(fu))) // Возвращаем распакованные значения.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_target_update() {
+        assert_eq!(TargetUpdate::parse("soft:0.005").unwrap(), TargetUpdate::Soft { tau: 0.005 });
+        assert_eq!(TargetUpdate::parse("hard:1000").unwrap(), TargetUpdate::Hard { every: 1000 });
+        for spec in ["hard:0", "hard:-1", "soft:0", "soft:1.5", "hard", "copy:10"] {
+            assert!(TargetUpdate::parse(spec).is_err(), "{spec} should be rejected");
+        }
+    }
+
+    #[test]
+    fn sequence_replay_stays_under_capacity() {
+        let mut sr = SequenceReplay::new(20, 8, 2);
+        let s = [0.0f32];
+        for _ in 0..20 {                                 // Эпизоды по одному шагу: 20 коротких кусков.
+            sr.push_step(&s, 0, 0.0, &s, true);
+        }
+        assert_eq!((sr.len(), sr.buf.len()), (20, 20));
+        for i in 0..37 {                                 // Длинный эпизод: куски по 8 шагов.
+            sr.push_step(&s, 1, i as f32, &s, false);
+            assert!(sr.len() <= 20, "{} transitions", sr.len());
+            assert_eq!(sr.len(), sr.buf.iter().map(|q| q.actions.len()).sum::<usize>());
+        }
+        // Остались самые свежие куски: два длинных и самые поздние короткие.
+        assert_eq!(sr.buf.iter().filter(|q| q.actions.len() == 8).count(), 2);
+        let last = sr.buf.iter().max_by_key(|q| q.rewards[0] as i32).unwrap();
+        assert_eq!(last.rewards[0], 24.0);
+    }
 }
@@ -0,0 +1,225 @@
+// Observation encoders: different ways to turn a `Game` into the network input.
+//
+//   rays           the hand-made ray features of `Game::observe`
+//   grid[+age]     full board, channels body / head / food / walls
+//   ego[:R][+age]  (2R+1)² crop around the head, rotated so the snake faces up;
+//                  channels body / food / walls (off-board cells count as walls)
+// `+age` adds a channel with each segment's age: 1.0 at the head, falling towards the
+// tail, so the network can tell which way the body will move.
+// Grid-like encodings are flattened channel-major (c, y, x) into one vector.
+// `FrameStack` concatenates the last K encodings, i.e. stacks frames along the channels.
+
+use std::collections::VecDeque;
+use crate::food::FoodKind;
+use crate::game::Game;
+
+/// Observation shape: channels × height × width (1 × 1 for flat features).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ObsShape {
+    pub channels: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl ObsShape {
+    /// Number of floats in one observation.
+    pub fn size(&self) -> usize { self.channels * self.height * self.width }
+
+    /// (channels, height, width) as used by `Net::from_spec`.
+    pub fn chw(&self) -> (usize, usize, usize) { (self.channels, self.height, self.width) }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ObsEncoder {
+    Rays,
+    Grid { age: bool },
+    Ego { radius: usize, age: bool },
+}
+
+impl ObsEncoder {
+    /// Parse `rays`, `grid`, `grid+age`, `ego`, `ego:5`, `ego:5+age`.
+    pub fn parse(spec: &str) -> Result<ObsEncoder, String> {
+        let (base, age) = match spec.strip_suffix("+age") {
+            Some(b) => (b, true),
+            None => (spec, false),
+        };
+        let (name, arg) = match base.split_once(':') {
+            Some((n, a)) => (n, Some(a)),
+            None => (base, None),
+        };
+        match (name, arg) {
+            ("rays", None) if !age => Ok(ObsEncoder::Rays),
+            ("grid", None) => Ok(ObsEncoder::Grid { age }),
+            ("ego", None) => Ok(ObsEncoder::Ego { radius: 4, age }),
+            ("ego", Some(r)) => match r.parse::<usize>() {
+                Ok(radius) if radius >= 1 => Ok(ObsEncoder::Ego { radius, age }),
+                _ => Err(format!("bad ego radius '{r}'")),
+            },
+            _ => Err(format!("unknown observation '{spec}' (expected rays, grid[+age] or ego[:R][+age])")),
+        }
+    }
+
+    /// Shape of the observation for this game.
+    pub fn shape(self, game: &Game) -> ObsShape {
+        match self {
+            ObsEncoder::Rays => ObsShape { channels: game.observation_dim(), height: 1, width: 1 },
+            ObsEncoder::Grid { age } => ObsShape { channels: 4 + age as usize, height: game.height(), width: game.width() },
+            ObsEncoder::Ego { radius, age } => {
+                let side = 2 * radius + 1;
+                ObsShape { channels: 3 + age as usize, height: side, width: side }
+            }
+        }
+    }
+
+    /// Length of the flattened observation.
+    pub fn dim(self, game: &Game) -> usize { self.shape(game).size() }
+
+    /// True if the shape depends on the board size (such encodings cannot change boards).
+    pub fn board_sized(self) -> bool { matches!(self, ObsEncoder::Grid { .. }) }
+
+    // The spec accepted by `parse`.
+    fn name(self) -> String {
+        match self {
+            ObsEncoder::Rays => "rays".to_string(),
+            ObsEncoder::Grid { age } => format!("grid{}", if age { "+age" } else { "" }),
+            ObsEncoder::Ego { radius, age } => format!("ego:{radius}{}", if age { "+age" } else { "" }),
+        }
+    }
+
+    /// Build the observation vector.
+    pub fn encode(self, game: &Game) -> Vec<f32> {
+        match self {
+            ObsEncoder::Rays => game.observe(),
+            ObsEncoder::Grid { age } => encode_grid(game, age),
+            ObsEncoder::Ego { radius, age } => encode_ego(game, radius, age),
+        }
+    }
+}
+
+/// Observation wrapper that concatenates the last `k` encodings (oldest first).
+/// The first observation of an episode fills the whole stack with copies of itself;
+/// call `reset` whenever the game is reset. `k = 1` is the bare encoder.
+#[derive(Clone, Debug)]
+pub struct FrameStack {
+    pub encoder: ObsEncoder,
+    pub k: usize,
+    frames: VecDeque<Vec<f32>>,
+}
+
+impl FrameStack {
+    pub fn new(encoder: ObsEncoder, k: usize) -> Self {
+        Self { encoder, k: k.max(1), frames: VecDeque::new() }
+    }
+
+    /// Shape of the stacked observation: `k` times the encoder's channels.
+    pub fn shape(&self, game: &Game) -> ObsShape {
+        let s = self.encoder.shape(game);
+        ObsShape { channels: s.channels * self.k, ..s }
+    }
+
+    /// Length of the stacked observation.
+    pub fn dim(&self, game: &Game) -> usize { self.shape(game).size() }
+
+    /// Name and shape for logs, e.g. `ego:4+age 4x9x9` or, when stacking, `rays x4 44x1x1`.
+    pub fn describe(&self, game: &Game) -> String {
+        let s = self.shape(game);
+        let stack = if self.k > 1 { format!(" x{}", self.k) } else { String::new() };
+        format!("{}{stack} {}x{}x{}", self.encoder.name(), s.channels, s.height, s.width)
+    }
+
+    /// Forget the previous episode's frames.
+    pub fn reset(&mut self) { self.frames.clear(); }
+
+    /// Encode the current state, push it as the newest frame and return the stack.
+    pub fn observe(&mut self, game: &Game) -> Vec<f32> {
+        let frame = self.encoder.encode(game);
+        if self.k == 1 { return frame; }
+        if self.frames.is_empty() {
+            self.frames.extend(std::iter::repeat_n(frame, self.k));
+        } else {
+            self.frames.pop_front();
+            self.frames.push_back(frame);
+        }
+        self.frames.iter().flatten().copied().collect()
+    }
+}
+
+// Per-cell segment age (0 = empty, 1 = head), row-major over the board.
+fn body_ages(game: &Game) -> Vec<f32> {
+    let (w, h) = (game.width(), game.height());
+    let segs = game.snake_segments(); // tail .. head
+    let mut ages = vec![0.0f32; w * h];
+    for (i, &(x, y)) in segs.iter().enumerate() {
+        if x >= 0 && y >= 0 && (x as usize) < w && (y as usize) < h {
+            ages[y as usize * w + x as usize] = (i + 1) as f32 / segs.len() as f32;
+        }
+    }
+    ages
+}
+
+// Food value of a cell: +1 apple/bonus, -1 poison.
+fn food_grid(game: &Game) -> Vec<f32> {
+    let w = game.width();
+    let mut food = vec![0.0f32; w * game.height()];
+    for f in game.foods() {
+        food[f.y * w + f.x] = if f.kind == FoodKind::Poison { -1.0 } else { 1.0 };
+    }
+    food
+}
+
+fn encode_grid(game: &Game, age: bool) -> Vec<f32> {
+    let (w, h) = (game.width(), game.height());
+    let n = w * h;
+    let ages = body_ages(game);
+    let food = food_grid(game);
+    let mut obs = vec![0.0f32; (4 + age as usize) * n];
+    for i in 0..n {
+        let (x, y) = ((i % w) as i32, (i / w) as i32);
+        obs[i] = (ages[i] > 0.0) as u8 as f32;            // body
+        obs[n + i] = (ages[i] == 1.0) as u8 as f32;       // head
+        obs[2 * n + i] = food[i];                         // food
+        obs[3 * n + i] = game.is_wall(x, y) as u8 as f32; // walls
+        if age {
+            obs[4 * n + i] = ages[i];
+        }
+    }
+    obs
+}
+
+fn encode_ego(game: &Game, radius: usize, age: bool) -> Vec<f32> {
+    let (w, h) = (game.width() as i32, game.height() as i32);
+    let segs = game.snake_segments();
+    let (hx, hy) = *segs.last().unwrap();
+    let dir = game.snake_dir();
+    let ages = body_ages(game);
+    let food = food_grid(game);
+
+    let side = 2 * radius + 1;
+    let n = side * side;
+    let mut obs = vec![0.0f32; (3 + age as usize) * n];
+    let r = radius as i32;
+    for row in 0..side {
+        for col in 0..side {
+            // Local frame: forward is up (row 0), right is the last column.
+            let (lx, ly) = (col as i32 - r, r - row as i32);
+            let (dx, dy) = dir.rotate(lx, ly);
+            let (mut x, mut y) = (hx + dx, hy + dy);
+            if game.is_wrap() {
+                x = x.rem_euclid(w);
+                y = y.rem_euclid(h);
+            }
+            let i = row * side + col;
+            if x < 0 || y < 0 || x >= w || y >= h || game.is_wall(x, y) {
+                obs[2 * n + i] = 1.0; // walls
+                continue;
+            }
+            let c = (y * w + x) as usize;
+            obs[i] = (ages[c] > 0.0) as u8 as f32; // body
+            obs[n + i] = food[c];                  // food
+            if age {
+                obs[3 * n + i] = ages[c];
+            }
+        }
+    }
+    obs
+}
@@ -6,6 +6,10 @@ use winit::{
 };
 use pixels::{Pixels, SurfaceTexture};
 use std::time::{Duration, Instant};
+use crate::arena::{Arena, Controller};
+use crate::demo::{DemoStep, DemoWriter};
+use crate::encoder::{FrameStack, ObsEncoder};
+use crate::food::FoodKind;
 use crate::game::*;
 use crate::snake::*;
 
@@ -16,26 +20,180 @@ const CELL_PX: u32 = 24;
 const STEP_MS_DEFAULT: u64 = 100;
 
 // Manual play in a separate window (arrow keys).
-pub fn run_manual(mut game: Game) -> Result<(), String> {
-    run_window_loop(game, None)
+// With a recorder, every step is logged as a demonstration (see `demo`).
+pub fn run_manual(game: Game, recorder: Option<DemoWriter>, encoder: ObsEncoder) -> Result<(), String> {
+    run_window_loop(game, None, recorder, FrameStack::new(encoder, 1))
 }
 
 // AI preview in a window (no learning).
 // NOTE: agent is passed BY VALUE to satisfy 'static closure requirement of winit.
-pub fn run_ai_preview(game: Game, agent: crate::dqn::DQNAgent) -> Result<(), String> {
-    run_window_loop(game, Some(agent))
+pub fn run_ai_preview(game: Game, agent: crate::dqn::DQNAgent, frames: FrameStack) -> Result<(), String> {
+    run_window_loop(game, Some(agent), None, frames)
+}
+
+// What `run_window` drives: one fixed-tick step, the 'R' reset and drawing.
+trait WindowSim {
+    // Advance one tick; `pending_dir` is the last arrow key (set it back when an episode ends).
+    fn tick(&mut self, pending_dir: &mut Dir);
+    fn reset(&mut self);
+    fn render(&self, frame: &mut [u8]);
 }
 
 // Unified window loop for manual and AI modes.
 // If `agent_opt` is Some(agent), we drive the game with the agent; otherwise with arrow keys.
+// `frames` builds the agent's (possibly stacked) and the recorder's observations.
 // We OWN agent here, so the 'static closure can freely move it.
-fn run_window_loop(mut game: Game, agent_opt: Option<crate::dqn::DQNAgent>) -> Result<(), String> {
-    let win_w = (game.width() as u32) * CELL_PX;
-    let win_h = (game.height() as u32) * CELL_PX;
+fn run_window_loop(
+    game: Game,
+    agent_opt: Option<crate::dqn::DQNAgent>,
+    recorder: Option<DemoWriter>,
+    frames: FrameStack,
+) -> Result<(), String> {
+    let title = if agent_opt.is_some() { "Snake — AI preview" } else { "Snake — manual" };
+    let (w, h) = (game.width(), game.height());
+    run_window(title, w, h, SingleSim { game, agent_opt, recorder, frames, ai_return: 0.0 })
+}
+
+// One game driven by the agent, by the arrow keys, or by the arrow keys with recording.
+struct SingleSim {
+    game: Game,
+    agent_opt: Option<crate::dqn::DQNAgent>,
+    recorder: Option<DemoWriter>,
+    frames: FrameStack,
+    ai_return: f32, // return of the current AI episode
+}
+
+impl WindowSim for SingleSim {
+    fn tick(&mut self, pending_dir: &mut Dir) {
+        let game = &mut self.game;
+        if let Some(agent) = self.agent_opt.as_mut() {
+            // AI-driven step.
+            let obs = self.frames.observe(game);
+            let a = agent.select_action(&obs, &game.safe_actions());
+            let StepOutcome { reward, done } = game.step_ai(a);
+            self.ai_return += reward;
+            if done {
+                // Print a short episode line to the console (overlay text is not drawn).
+                println!(
+                    "AI episode finished | return = {:.3} | score = {} | end = {} | seed = {}",
+                    self.ai_return, game.score(), game.end_name(), game.episode_seed(),
+                );
+                self.ai_return = 0.0;
+                game.reset();
+                self.frames.reset();
+                agent.reset_episode();
+            }
+        } else if let Some(rec) = self.recorder.as_mut() {
+            // Recorded manual step: go through the RL interface so the
+            // demonstration has the same rewards/termination as training.
+            let obs = self.frames.encoder.encode(game);
+            let action = game.relative_action(*pending_dir);
+            let StepOutcome { reward, done } = game.step_ai(action);
+            let step = DemoStep { obs, action, reward, next_obs: self.frames.encoder.encode(game), done };
+            if let Err(e) = rec.push(&step) {
+                eprintln!("Error writing demonstration: {e}");
+            }
+            if done {
+                println!(
+                    "Recorded episode finished | score = {} | end = {} | seed = {} | total steps in file = {}",
+                    game.score(), game.end_name(), game.episode_seed(), rec.steps,
+                );
+                game.reset();
+                *pending_dir = Dir::Right;
+            }
+        } else {
+            // Manual step.
+            game.set_pending_dir(*pending_dir);
+            game.step();
+            if game.is_done() {
+                println!(
+                    "Episode finished | score = {} | end = {} | seed = {}",
+                    game.score(), game.end_name(), game.episode_seed(),
+                );
+                game.reset();
+                *pending_dir = Dir::Right;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ai_return = 0.0;
+        self.game.reset();
+        self.frames.reset();
+        if let Some(agent) = self.agent_opt.as_mut() { agent.reset_episode(); }
+    }
+
+    fn render(&self, frame: &mut [u8]) {
+        render_frame(frame, &self.game);
+    }
+}
+
+// Arena in a window: the human snake (if any) follows the arrow keys, agent snakes use
+// `agent`, bots play their scripted policy. No learning.
+pub fn run_arena(
+    arena: Arena,
+    controllers: Vec<Controller>,
+    agent: Option<crate::dqn::DQNAgent>,
+) -> Result<(), String> {
+    let (w, h) = (arena.width(), arena.height());
+    run_window("Snake — arena", w, h, ArenaSim { arena, controllers, agent })
+}
+
+struct ArenaSim {
+    arena: Arena,
+    controllers: Vec<Controller>,
+    agent: Option<crate::dqn::DQNAgent>,
+}
+
+impl WindowSim for ArenaSim {
+    fn tick(&mut self, pending_dir: &mut Dir) {
+        let arena = &mut self.arena;
+        // One action per living snake from its controller.
+        let actions: Vec<u8> = self.controllers.iter().enumerate().map(|(i, c)| {
+            if !arena.is_alive(i) {
+                return 1;
+            }
+            match (c, self.agent.as_mut()) {
+                (Controller::Human, _) => arena.relative_action(i, *pending_dir),
+                (Controller::Agent, Some(ag)) => ag.select_action(&arena.observe(i), &arena.safe_actions(i)),
+                _ => arena.bot_action(i),
+            }
+        }).collect();
+        arena.step(&actions);
+
+        if arena.is_over() {
+            let results: Vec<String> = self.controllers.iter().enumerate()
+                .map(|(i, c)| format!("{}#{} {} ({})", c.name(), i, arena.score(i), arena.end_name(i)))
+                .collect();
+            println!(
+                "Arena episode finished | {} | winner = {} | seed = {}",
+                results.join(", "),
+                arena.winner().map_or("-".to_string(), |w| format!("#{w}")),
+                arena.episode_seed(),
+            );
+            arena.reset();
+            *pending_dir = Dir::Right;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.arena.reset();
+    }
+
+    fn render(&self, frame: &mut [u8]) {
+        render_arena(frame, &self.arena);
+    }
+}
+
+// Window, pixel buffer, fixed-tick timing and the shared keys (arrows, R reset, Space pause,
+// +/- speed, Esc) around a `WindowSim` on a `w`×`h` board.
+fn run_window(title: &str, w: usize, h: usize, mut sim: impl WindowSim + 'static) -> Result<(), String> {
+    let win_w = (w as u32) * CELL_PX;
+    let win_h = (h as u32) * CELL_PX;
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
-        .with_title(if agent_opt.is_some() { "Snake — AI preview" } else { "Snake — manual" })
+        .with_title(title)
         .with_inner_size(LogicalSize::new(win_w as f64, win_h as f64))
         .with_min_inner_size(LogicalSize::new(win_w as f64, win_h as f64))
         .build(&event_loop)
@@ -55,21 +213,13 @@ fn run_window_loop(mut game: Game, agent_opt: Option<crate::dqn::DQNAgent>) -> R
     let mut prev = Instant::now();
     let mut paused = false;
 
-    // Simple counters for AI preview.
-    let mut ai_return: f32 = 0.0;
-
-    // Make agent_opt mutable and move it into the closure (owned, 'static).
-    let mut agent_opt = agent_opt;
-
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
         match event {
             Event::RedrawRequested(_) => {
-                // Draw current game state into the RGBA buffer.
-                let frame = pixels.frame_mut();
-                render_frame(frame, &game);
-
+                // Draw current state into the RGBA buffer.
+                sim.render(pixels.frame_mut());
                 if let Err(e) = pixels.render() {
                     eprintln!("Error rendering frame: {e}");
                     *control_flow = ControlFlow::Exit;
@@ -87,29 +237,8 @@ fn run_window_loop(mut game: Game, agent_opt: Option<crate::dqn::DQNAgent>) -> R
                 // Advance the game logic with a fixed tick.
                 while acc >= step_dt {
                     acc -= step_dt;
-
                     if !paused {
-                        if let Some(agent) = agent_opt.as_mut() {
-                            // AI-driven step.
-                            let obs = game.observe();
-                            let a = agent.select_action(&obs);
-                            let StepOutcome { reward, done } = game.step_ai(a);
-                            ai_return += reward;
-                            if done {
-                                // Print a short episode line to the console (overlay text is not drawn).
-                                println!("AI episode finished | return = {:.3}", ai_return);
-                                ai_return = 0.0;
-                                game.reset();
-                            }
-                        } else {
-                            // Manual step.
-                            game.set_pending_dir(pending_dir);
-                            game.step();
-                            if game.is_done() {
-                                game.reset();
-                                pending_dir = Dir::Right;
-                            }
-                        }
+                        sim.tick(&mut pending_dir);
                     }
                 }
 
@@ -130,50 +259,46 @@ fn run_window_loop(mut game: Game, agent_opt: Option<crate::dqn::DQNAgent>) -> R
                 WindowEvent::KeyboardInput {
                     input:
                     KeyboardInput {
-                        state,
+                        state: ElementState::Pressed,
                         virtual_keycode: Some(key),
                         ..
                     },
                     ..
-                } => {
-                    if state == ElementState::Pressed {
-                        match key {
-                            // Manual arrows (ignored in AI mode, but harmless to set).
-                            VirtualKeyCode::Up    => { pending_dir = Dir::Up; }
-                            VirtualKeyCode::Down  => { pending_dir = Dir::Down; }
-                            VirtualKeyCode::Left  => { pending_dir = Dir::Left; }
-                            VirtualKeyCode::Right => { pending_dir = Dir::Right; }
-
-                            // Reset episode.
-                            VirtualKeyCode::R => {
-                                ai_return = 0.0;
-                                game.reset();
-                            }
-
-                            // Pause/resume.
-                            VirtualKeyCode::Space => {
-                                paused = !paused;
-                            }
-
-                            // Speed up: '=' or Numpad '+'
-                            VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
-                                step_ms = (step_ms.saturating_sub(10)).max(20);
-                                println!("speed: {} ms/step", step_ms);
-                            }
-
-                            // Slow down: '-' or Numpad '-'
-                            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
-                                step_ms = (step_ms + 10).min(500);
-                                println!("speed: {} ms/step", step_ms);
-                            }
-
-                            // Exit.
-                            VirtualKeyCode::Escape => {
-                                *control_flow = ControlFlow::Exit;
-                            }
-                            _ => {}
-                        }
+                } => match key {
+                    // Manual arrows (ignored in AI mode, but harmless to set).
+                    VirtualKeyCode::Up    => { pending_dir = Dir::Up; }
+                    VirtualKeyCode::Down  => { pending_dir = Dir::Down; }
+                    VirtualKeyCode::Left  => { pending_dir = Dir::Left; }
+                    VirtualKeyCode::Right => { pending_dir = Dir::Right; }
+
+                    // Reset episode.
+                    VirtualKeyCode::R => {
+                        sim.reset();
+                        pending_dir = Dir::Right;
+                    }
+
+                    // Pause/resume.
+                    VirtualKeyCode::Space => {
+                        paused = !paused;
+                    }
+
+                    // Speed up: '=' or Numpad '+'
+                    VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
+                        step_ms = (step_ms.saturating_sub(10)).max(20);
+                        println!("speed: {} ms/step", step_ms);
+                    }
+
+                    // Slow down: '-' or Numpad '-'
+                    VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                        step_ms = (step_ms + 10).min(500);
+                        println!("speed: {} ms/step", step_ms);
+                    }
+
+                    // Exit.
+                    VirtualKeyCode::Escape => {
+                        *control_flow = ControlFlow::Exit;
                     }
+                    _ => {}
                 }
                 _ => {}
             },
@@ -202,9 +327,33 @@ fn render_frame(frame: &mut [u8], game: &Game) {
         fill_rect(frame, win_w, win_h, 0, y, win_w, 1, [30, 30, 36, 255]);
     }
 
-    // Food (red).
-    let (fx, fy) = game.food_pos();
-    draw_cell(frame, win_w, win_h, fx as u32, fy as u32, [200, 60, 36, 255]);
+    // Wrapping board: mark the edges as portals (teal frame) instead of walls.
+    if game.is_wrap() {
+        let col = [40, 110, 120, 255];
+        fill_rect(frame, win_w, win_h, 0, 0, win_w, 2, col);
+        fill_rect(frame, win_w, win_h, 0, win_h - 2, win_w, 2, col);
+        fill_rect(frame, win_w, win_h, 0, 0, 2, win_h, col);
+        fill_rect(frame, win_w, win_h, win_w - 2, 0, 2, win_h, col);
+    }
+
+    // Interior walls from the level map (grey).
+    for y in 0..game.height() {
+        for x in 0..game.width() {
+            if game.is_wall(x as i32, y as i32) {
+                draw_cell(frame, win_w, win_h, x as u32, y as u32, [90, 90, 100, 255]);
+            }
+        }
+    }
+
+    // Food: apples red, bonus gold, poison purple.
+    for f in game.foods() {
+        let col = match f.kind {
+            FoodKind::Apple => [200, 60, 36, 255],
+            FoodKind::Bonus => [230, 190, 40, 255],
+            FoodKind::Poison => [150, 60, 190, 255],
+        };
+        draw_cell(frame, win_w, win_h, f.x as u32, f.y as u32, col);
+    }
 
     // Snake (green). Head a bit brighter.
     let segs = game.snake_segments();
@@ -215,6 +364,41 @@ fn render_frame(frame: &mut [u8], game: &Game) {
     }
 }
 
+// Draw the arena: apples and every living snake in its own colour.
+fn render_arena(frame: &mut [u8], arena: &Arena) {
+    const COLORS: [[u8; 3]; 6] = [
+        [60, 180, 90], [70, 130, 220], [220, 140, 50], [200, 80, 180], [80, 200, 200], [210, 210, 90],
+    ];
+    let win_w = (arena.width() as u32) * CELL_PX;
+    let win_h = (arena.height() as u32) * CELL_PX;
+
+    fill_rect(frame, win_w, win_h, 0, 0, win_w, win_h, [18, 18, 22, 255]);
+    for gx in 0..=arena.width() as u32 {
+        fill_rect(frame, win_w, win_h, gx * CELL_PX, 0, 1, win_h, [30, 30, 36, 255]);
+    }
+    for gy in 0..=arena.height() as u32 {
+        fill_rect(frame, win_w, win_h, 0, gy * CELL_PX, win_w, 1, [30, 30, 36, 255]);
+    }
+
+    for f in arena.foods() {
+        draw_cell(frame, win_w, win_h, f.x as u32, f.y as u32, [200, 60, 36, 255]);
+    }
+
+    for i in (0..arena.num_snakes()).filter(|&i| arena.is_alive(i)) {
+        let [r, g, b] = COLORS[i % COLORS.len()];
+        let segs = arena.snake_segments(i);
+        for (k, (x, y)) in segs.iter().enumerate() {
+            // Head a bit brighter.
+            let col = if k + 1 == segs.len() {
+                [r.saturating_add(30), g.saturating_add(30), b.saturating_add(30), 255]
+            } else {
+                [r, g, b, 255]
+            };
+            draw_cell(frame, win_w, win_h, *x as u32, *y as u32, col);
+        }
+    }
+}
+
 // Draw one cell in grid coordinates.
 fn draw_cell(frame: &mut [u8], win_w: u32, win_h: u32, cx: u32, cy: u32, rgba: [u8; 4]) {
     let x0 = cx * CELL_PX;
@@ -223,6 +407,7 @@ fn draw_cell(frame: &mut [u8], win_w: u32, win_h: u32, cx: u32, cy: u32, rgba: [
 }
 
 // Fill an axis-aligned rectangle in the pixel buffer (coords in pixels).
+#[allow(clippy::too_many_arguments)]
 fn fill_rect(frame: &mut [u8], win_w: u32, win_h: u32, x: u32, y: u32, w: u32, h: u32, rgba: [u8; 4]) {
     let x1 = (x + w).min(win_w);
     let y1 = (y + h).min(win_h);
@@ -231,7 +416,7 @@ fn fill_rect(frame: &mut [u8], win_w: u32, win_h: u32, x: u32, y: u32, w: u32, h
         let row_off = (py as usize) * (win_w as usize) * 4;
         for px in x..x1 {
             let off = row_off + (px as usize) * 4;
-            frame[off + 0] = rgba[0]; // R
+            frame[off]     = rgba[0]; // R
             frame[off + 1] = rgba[1]; // G
             frame[off + 2] = rgba[2]; // B
             frame[off + 3] = rgba[3]; // A
@@ -0,0 +1,152 @@
+// Gradient-free training of `Net` weights with OpenAI-style evolution strategies.
+//
+// One generation:
+//   1) sample `pairs` Gaussian directions ε_i and build antithetic candidates θ ± σ ε_i;
+//   2) fitness = mean greedy score over the same K games (evaluated in parallel);
+//   3) centered-rank fitness shaping, θ ← θ + lr / (2 n σ) Σ (u⁺_i − u⁻_i) ε_i.
+// The best center found so far is written as a regular weights.bin checkpoint.
+
+use crate::encoder::ObsEncoder;
+use crate::game::{Game, GameConfig};
+use crate::log;
+use crate::network::{LayerSpec, Net};
+use crate::utils::*;
+
+/// ES hyperparameters.
+pub struct EsConfig {
+    pub game: GameConfig,      // settings of the evaluation games
+    pub encoder: ObsEncoder,   // net input encoding
+    pub arch: Vec<LayerSpec>,  // hidden layers of the evolved Net
+    pub pairs: usize,          // antithetic pairs per generation (population = 2 * pairs)
+    pub sigma: f32,            // noise scale
+    pub lr: f32,               // step size on the center
+    pub games: usize,          // K games per fitness evaluation (game k uses seed game.seed + k)
+    pub max_steps: u64,        // safety cap per game
+    pub threads: usize,        // worker threads for evaluation
+    pub seed: u64,             // noise RNG seed
+    pub out_path: String,      // checkpoint path (weights.bin format)
+}
+
+pub struct EsTrainer {
+    cfg: EsConfig,
+    net: Net,         // holds the current center θ (and is what gets saved)
+    theta: Vec<f32>,
+    rng: LcgRng,
+    best_fitness: f32,
+    pub generation: u64,
+}
+
+impl EsTrainer {
+    /// Create a trainer; the center starts from `out_path` if it can be loaded.
+    pub fn new(cfg: EsConfig) -> Self {
+        let shape = cfg.encoder.shape(&Game::from_config(&cfg.game)).chw();
+        let mut net = Net::from_spec(shape, &cfg.arch, 3, LcgRng::new(cfg.seed ^ 0x5EED));
+        if net.load(&cfg.out_path).is_ok() {
+            log::info(&format!("es: starting from {}", cfg.out_path));
+        }
+        log::info(&format!("es: network {}", net.describe()));
+        let theta = net.params_flat();
+        let rng = LcgRng::new(cfg.seed);
+        Self { cfg, net, theta, rng, best_fitness: f32::NEG_INFINITY, generation: 0 }
+    }
+
+    /// Run one generation; returns (mean population fitness, center fitness).
+    pub fn step(&mut self) -> (f32, f32) {
+        let n = self.cfg.pairs;
+        let dim = self.theta.len();
+
+        // Noise directions and antithetic candidates.
+        let mut noise: Vec<Vec<f32>> = Vec::with_capacity(n);
+        let mut cands: Vec<Vec<f32>> = Vec::with_capacity(2 * n);
+        for _ in 0..n {
+            let eps: Vec<f32> = (0..dim).map(|_| self.rng.next_gaussian()).collect();
+            cands.push(self.theta.iter().zip(&eps).map(|(t, e)| t + self.cfg.sigma * e).collect());
+            cands.push(self.theta.iter().zip(&eps).map(|(t, e)| t - self.cfg.sigma * e).collect());
+            noise.push(eps);
+        }
+        // The center is evaluated alongside the population.
+        cands.push(self.theta.clone());
+
+        let fit = self.evaluate_all(&cands);
+        let center_fit = fit[2 * n];
+        let pop_fit = &fit[..2 * n];
+
+        // Centered ranks in [-0.5, 0.5] (ties share the average rank).
+        let util = centered_ranks(pop_fit);
+        let mut grad = vec![0.0f32; dim];
+        for i in 0..n {
+            let u = util[2 * i] - util[2 * i + 1];
+            if u == 0.0 { continue; }
+            for (g, e) in grad.iter_mut().zip(&noise[i]) { *g += u * e; }
+        }
+        let k = self.cfg.lr / (2.0 * n as f32 * self.cfg.sigma);
+        for (t, g) in self.theta.iter_mut().zip(&grad) { *t += k * g; }
+
+        // Keep the best center as the checkpoint (params before this update).
+        if center_fit > self.best_fitness {
+            self.best_fitness = center_fit;
+            self.net.set_params_flat(&cands[2 * n]);
+            if self.net.save(&self.cfg.out_path).is_ok() {
+                log::info(&format!("es: saved {} (fitness {:.3})", self.cfg.out_path, center_fit));
+            }
+        }
+
+        self.generation += 1;
+        let mean = pop_fit.iter().sum::<f32>() / pop_fit.len().max(1) as f32;
+        (mean, center_fit)
+    }
+
+    // Fitness of every candidate, spread over worker threads.
+    fn evaluate_all(&self, cands: &[Vec<f32>]) -> Vec<f32> {
+        let threads = self.cfg.threads.max(1);
+        let chunk = cands.len().div_ceil(threads);
+        let mut fit = vec![0.0f32; cands.len()];
+        std::thread::scope(|s| {
+            for (cs, out) in cands.chunks(chunk).zip(fit.chunks_mut(chunk)) {
+                s.spawn(move || {
+                    let mut net = self.net.clone();
+                    for (p, f) in cs.iter().zip(out.iter_mut()) {
+                        net.set_params_flat(p);
+                        *f = self.fitness(&mut net);
+                    }
+                });
+            }
+        });
+        fit
+    }
+
+    // Mean greedy score over K seeded games; every candidate is scored on identical episodes.
+    fn fitness(&self, net: &mut Net) -> f32 {
+        let mut game = Game::from_config(&self.cfg.game);
+        let mut total = 0.0f32;
+        for k in 0..self.cfg.games {
+            game.reset_with_seed(self.cfg.game.seed.wrapping_add(k as u64));
+            for _ in 0..self.cfg.max_steps {
+                let q = net.forward(&self.cfg.encoder.encode(&game));
+                let a = if has_non_finite(&q) { 1 } else { argmax(&q) as u8 };
+                if game.step_ai(a).done { break; }
+            }
+            total += game.score() as f32;
+        }
+        total / self.cfg.games.max(1) as f32
+    }
+}
+
+// Centered-rank transform used by OpenAI-ES.
+fn centered_ranks(xs: &[f32]) -> Vec<f32> {
+    let n = xs.len();
+    if n < 2 { return vec![0.0; n]; }
+    let mut idx: Vec<usize> = (0..n).collect();
+    idx.sort_by(|&a, &b| xs[a].total_cmp(&xs[b]));
+    let mut ranks = vec![0.0f32; n];
+    let mut i = 0;
+    while i < n {
+        // Group equal values and give them their average rank.
+        let mut j = i;
+        while j + 1 < n && xs[idx[j + 1]] == xs[idx[i]] { j += 1; }
+        let avg = (i + j) as f32 / 2.0;
+        for &k in &idx[i..=j] { ranks[k] = avg; }
+        i = j + 1;
+    }
+    ranks.iter().map(|r| r / (n - 1) as f32 - 0.5).collect()
+}
@@ -0,0 +1,97 @@
+// Exploration strategies of the DQN agent.
+//
+//   eps                  ε-greedy with ε from the agent's ε schedule
+//   softmax:SCHED        Boltzmann over Q with a temperature schedule, e.g. softmax:exp:1:0.05:100000
+//   episode:LO:HI        ε-greedy with ε drawn uniformly from [LO, HI] at the start of every episode
+// A `+safe` suffix restricts random choices to actions that do not die on the next step
+// (as reported by the game), e.g. `eps+safe`; if every action is fatal, all are allowed.
+
+use std::fmt;
+use crate::schedule::Schedule;
+use crate::utils::LcgRng;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Strategy {
+    EpsGreedy,
+    Softmax(Schedule),
+    EpisodeEps { lo: f32, hi: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exploration {
+    pub strategy: Strategy,
+    pub safe: bool,
+}
+
+impl Default for Exploration {
+    fn default() -> Self { Exploration { strategy: Strategy::EpsGreedy, safe: false } }
+}
+
+impl Exploration {
+    /// Parse a strategy spec (see the module comment).
+    pub fn parse(spec: &str) -> Result<Exploration, String> {
+        let (base, safe) = match spec.strip_suffix("+safe") {
+            Some(b) => (b, true),
+            None => (spec, false),
+        };
+        let strategy = if base == "eps" {
+            Strategy::EpsGreedy
+        } else if let Some(sch) = base.strip_prefix("softmax:") {
+            Strategy::Softmax(Schedule::parse(sch)?)
+        } else if let Some(range) = base.strip_prefix("episode:") {
+            let bad = || format!("bad ε range '{range}' (expected LO:HI within [0, 1])");
+            let (lo, hi) = range.split_once(':').ok_or_else(bad)?;
+            let (lo, hi): (f32, f32) = (lo.parse().map_err(|_| bad())?, hi.parse().map_err(|_| bad())?);
+            if !(0.0..=hi).contains(&lo) || hi > 1.0 {
+                return Err(bad());
+            }
+            Strategy::EpisodeEps { lo, hi }
+        } else {
+            return Err(format!("unknown exploration '{spec}' (expected eps, softmax:SCHED or episode:LO:HI, optionally +safe)"));
+        };
+        Ok(Exploration { strategy, safe })
+    }
+}
+
+/// Inverse of `parse`.
+impl fmt::Display for Exploration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.strategy {
+            Strategy::EpsGreedy => write!(f, "eps")?,
+            Strategy::Softmax(sch) => write!(f, "softmax:{sch}")?,
+            Strategy::EpisodeEps { lo, hi } => write!(f, "episode:{lo}:{hi}")?,
+        }
+        if self.safe { write!(f, "+safe")?; }
+        Ok(())
+    }
+}
+
+/// Uniformly random action among the allowed ones (all of them if none is allowed).
+pub fn random_action(allowed: &[bool], rng: &mut LcgRng) -> usize {
+    let n = allowed.iter().filter(|&&a| a).count();
+    if n == 0 {
+        return rng.gen_range_u32(allowed.len() as u32) as usize;
+    }
+    let k = rng.gen_range_u32(n as u32) as usize;
+    allowed.iter().enumerate().filter(|(_, &a)| a).nth(k).map_or(0, |(i, _)| i)
+}
+
+/// Sample from softmax(q / temp) over the allowed actions (all of them if none is allowed);
+/// a non-positive temperature is greedy.
+pub fn softmax_action(q: &[f32], temp: f32, allowed: &[bool], rng: &mut LcgRng) -> usize {
+    let any = allowed.iter().any(|&a| a);
+    let ok = |i: usize| !any || allowed[i];
+    let qmax = (0..q.len()).filter(|&i| ok(i)).map(|i| q[i]).fold(f32::NEG_INFINITY, f32::max);
+    if temp <= 0.0 {
+        return (0..q.len()).find(|&i| ok(i) && q[i] == qmax).unwrap_or(0);
+    }
+    let w: Vec<f32> = (0..q.len())
+        .map(|i| if ok(i) { ((q[i] - qmax) / temp).exp() } else { 0.0 })
+        .collect();
+    let mut u = rng.next_f32() * w.iter().sum::<f32>();
+    for (i, wi) in w.iter().enumerate() {
+        if u < *wi { return i; }
+        u -= wi;
+    }
+    (0..q.len()).rev().find(|&i| ok(i)).unwrap_or(0)
+}
@@ -1,8 +1,18 @@
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FoodKind {
+    Apple,  // +1 point, grows the snake
+    Bonus,  // worth more, disappears when its ttl runs out
+    Poison, // shrinks the snake
+}
+
+#[derive(Clone)]
 pub struct Food {
     pub x: usize,
     pub y: usize,
+    pub kind: FoodKind,
+    pub ttl: u32, // steps left before a bonus expires (0 = never)
 }
 
 impl Food {
-    pub fn at(x: usize, y: usize) -> Self {Self {x, y}}
-}
\ No newline at end of file
+    pub fn new(x: usize, y: usize, kind: FoodKind, ttl: u32) -> Self {Self {x, y, kind, ttl}}
+}
@@ -1,25 +1,107 @@
 // Game rules module: state, steps, collisions, food, spawn.
 
+use std::sync::Arc;
 use crate::food::*;
+use crate::level::Level;
 use crate::snake::*;
 use crate::utils::*;
 
+/// Game settings: board size, start length, hunger limit, seed, edge mode and level.
+#[derive(Clone)]
+pub struct GameConfig {
+    pub w: usize,
+    pub h: usize,
+    pub start_len: usize,  // initial snake length (clamped to fit the board)
+    pub hunger_limit: u32, // max steps without food in RL mode
+    pub seed: u64,         // seed of the first episode; later episodes derive from it
+    pub wrap: bool,        // toroidal board: leaving an edge re-enters at the opposite one
+    pub level: Option<Arc<Level>>, // interior walls / start / food spots; its size overrides w, h
+    pub apples: usize,     // apples on the board at once
+    pub bonus_chance: f32, // per-step chance that a bonus appears (at most one at a time)
+    pub bonus_ttl: u32,    // steps a bonus stays on the board
+    pub poison: usize,     // poison items on the board (eaten ones respawn elsewhere)
+}
+
+impl GameConfig {
+    /// Defaults for a `w`×`h` board: length-3 snake, 200-step hunger limit, solid walls.
+    pub fn new(w: usize, h: usize) -> Self {
+        Self {
+            w, h, start_len: 3, hunger_limit: 200, seed: DEFAULT_SEED, wrap: false, level: None,
+            apples: 1, bonus_chance: 0.0, bonus_ttl: 40, poison: 0,
+        }
+    }
+
+    /// Bonus or poison food is enabled (the observation then gets extra channels).
+    pub fn typed_food(&self) -> bool { self.bonus_chance > 0.0 || self.poison > 0 }
+}
+
+/// Default seed of `GameConfig::new`.
+pub const DEFAULT_SEED: u64 = 0xC0FFEE;
+
+/// Reward for filling the whole board (on top of the food reward).
+const CLEAR_REWARD: f32 = 5.0;
+
+/// Points (and reward) for a bonus item.
+pub const BONUS_POINTS: u32 = 3;
+
+/// Segments lost and reward for eating poison.
+const POISON_SHRINK: usize = 1;
+const POISON_REWARD: f32 = -0.5;
+
+/// Why an episode ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EndReason {
+    Wall,          // head left the board or hit an interior wall
+    SelfCollision, // head ran into the body
+    Starvation,    // hunger limit reached (RL mode)
+    BoardCleared,  // the snake fills every cell: win
+    Opponent,      // head ran into another snake (arena)
+}
+
+impl EndReason {
+    /// Short name for logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            EndReason::Wall => "wall",
+            EndReason::SelfCollision => "self",
+            EndReason::Starvation => "starved",
+            EndReason::BoardCleared => "cleared",
+            EndReason::Opponent => "opponent",
+        }
+    }
+}
+
 /// Result of an RL step: immediate reward and termination flag.
 pub struct StepOutcome {
     pub reward: f32,
     pub done: bool,
 }
 
+/// Full game state. Cloning is cheap (a few small vectors plus scalars; the level is shared)
+/// and copies the food RNG as well, so a clone evolves exactly like the original under
+/// the same actions.
+#[derive(Clone)]
 pub struct Game {
     // Grid size (in cells).
     w: usize,
     h: usize,
 
+    // Toroidal board (no lethal walls).
+    wrap: bool,
+
+    // Level map (interior walls, start cell, food spots), shared between clones.
+    level: Option<Arc<Level>>,
+
     // Snake state.
     snake: Snake,
 
-    // Food position.
-    food: Food,
+    // Food items on the board and how many of each kind to keep there.
+    foods: Vec<Food>,
+    apples: usize,
+    bonus_chance: f32,
+    bonus_ttl: u32,
+    poison: usize,
+    typed_food: bool, // bonus/poison enabled: extra observation channels
 
     // Buffered direction from keyboard (used by step()).
     pending_dir: Dir,
@@ -27,50 +109,103 @@ pub struct Game {
     // Terminal flag for manual mode (UI loop).
     done: bool,
 
-    // RNG for food spawning.
+    // Why the last episode ended (None while it is running).
+    end: Option<EndReason>,
+
+    // Initial snake length for every episode.
+    start_len: usize,
+
+    // RNG for food spawning, re-seeded from `episode_seed` at every reset.
     rng: LcgRng,
+    episode_seed: u64,
 
     // ---- Extra fields for RL shaping ----
     steps_since_food: u32, // how many steps since last apple
     hunger_limit: u32,     // max steps without food before terminating the episode
     last_manhattan: i32,   // previous Manhattan distance to food (for shaping)
+    score: u32,            // apples eaten in the current episode
 }
 
 impl Game {
-    /// Create a new game with centered snake and one food.
-    pub fn new(w: usize, h: usize) -> Self {
-        let mut rng = LcgRng::new(0xC0FFEE_u64);
-        let snake = Snake::new((w / 2) as i32, (h / 2) as i32);
+    /// Create a game from explicit settings.
+    pub fn from_config(cfg: &GameConfig) -> Self {
+        // A level fixes the board size.
+        let (w, h) = match &cfg.level {
+            Some(l) => (l.w, l.h),
+            None => (cfg.w, cfg.h),
+        };
+        // The snake lies horizontally, head on the level's start cell or right of center;
+        // its length is clamped so the tail stays on the board.
+        let max_len = match &cfg.level {
+            Some(l) => l.spawn_head().0 as usize + 1,
+            None => w / 2 + 2,
+        };
+        let start_len = cfg.start_len.clamp(1, max_len);
         let mut g = Self {
             w,
             h,
-            snake,
-            food: Food::at(0, 0),
+            wrap: cfg.wrap,
+            level: cfg.level.clone(),
+            snake: Snake::new(0, 0, 1, w, h), // replaced by reset_with_seed below
+            foods: Vec::new(),
+            apples: cfg.apples.max(1),
+            bonus_chance: cfg.bonus_chance,
+            bonus_ttl: cfg.bonus_ttl.max(1),
+            poison: cfg.poison,
+            typed_food: cfg.typed_food(),
             pending_dir: Dir::Right,
             done: false,
-            rng,
+            end: None,
+            start_len,
+            rng: LcgRng::new(0),
+            episode_seed: cfg.seed,
             steps_since_food: 0,
-            hunger_limit: 200,
+            hunger_limit: cfg.hunger_limit,
             last_manhattan: 0,
+            score: 0,
         };
-        // Spawn initial food and compute initial Manhattan distance.
-        g.respawn_food();
-        let (hx, hy) = g.snake.head();
-        g.last_manhattan = (g.food.x as i32 - hx).abs() + (g.food.y as i32 - hy).abs();
+        // Spawn the snake and initial food for the first episode.
+        g.reset_with_seed(cfg.seed);
         g
     }
 
-    /// Reset to the initial state.
+    /// Reset to the initial state. Each episode gets a fresh seed derived from the
+    /// previous one, so a run is fully determined by `GameConfig::seed`.
     pub fn reset(&mut self) {
-        self.snake = Snake::new((self.w / 2) as i32, (self.h / 2) as i32);
+        self.reset_with_seed(mix_seed(self.episode_seed));
+    }
+
+    /// Reset and play the next episode with exactly this seed (see `episode_seed`).
+    pub fn reset_with_seed(&mut self, seed: u64) {
+        self.episode_seed = seed;
+        self.rng = LcgRng::new(mix_seed(seed));
+        let (cx, cy) = match &self.level {
+            Some(l) => (l.spawn_head().0 - 1, l.spawn_head().1),
+            None => ((self.w / 2) as i32, (self.h / 2) as i32),
+        };
+        self.snake = Snake::new(cx, cy, self.start_len, self.w, self.h);
+        self.snake.set_wrap(self.wrap);
         self.pending_dir = Dir::Right;
         self.done = false;
+        self.end = None;
         self.steps_since_food = 0;
-        self.respawn_food();
-        let (hx, hy) = self.snake.head();
-        self.last_manhattan = (self.food.x as i32 - hx).abs() + (self.food.y as i32 - hy).abs();
+        self.score = 0;
+        self.foods.clear();
+        for _ in 0..self.apples {
+            self.spawn_food(FoodKind::Apple, 0);
+        }
+        for _ in 0..self.poison {
+            self.spawn_food(FoodKind::Poison, 0);
+        }
+        self.last_manhattan = self.food_distance();
     }
 
+    /// Take a snapshot of the whole state (for lookahead search).
+    pub fn snapshot(&self) -> Game { self.clone() }
+
+    /// Restore a state previously taken with `snapshot`.
+    pub fn restore(&mut self, snap: &Game) { self.clone_from(snap); }
+
     /// O ne logical step for manual mode (uses `pending_dir`).
     pub fn step(&mut self) {
         if self.done {
@@ -83,24 +218,27 @@ impl Game {
         // Move the snake forward by one cell.
         self.snake.advance();
 
-        // Check wall collision.
+        // Check wall collision (board edges never kill on a wrapping board).
         let (hx, hy) = self.snake.head();
-        if hx < 0 || hy < 0 || hx >= self.w as i32 || hy >= self.h as i32 {
-            self.done = true;
+        if self.out_of_bounds(hx, hy) || self.is_wall(hx, hy) {
+            self.finish(EndReason::Wall);
             return;
         }
 
         // Check self-collision.
         if self.snake.self_collision() {
-            self.done = true;
+            self.finish(EndReason::SelfCollision);
             return;
         }
 
         // Check food.
-        if (hx as usize, hy as usize) == (self.food.x, self.food.y) {
-            self.snake.feed();   // grow on the next move
-            self.respawn_food(); // place a new food
+        if let Some((_, cleared)) = self.eat(hx, hy) {
+            if cleared { // no free cell left: the board is cleared
+                self.finish(EndReason::BoardCleared);
+                return;
+            }
         }
+        self.tick_bonus();
     }
 
     // ---------------- RL mode ----------------
@@ -129,32 +267,40 @@ impl Game {
         // Advance one cell.
         self.snake.advance();
 
-        // Wall collision ends the episode with negative reward.
+        // Wall collision (edge or interior wall) ends the episode with negative reward.
         let (hx, hy) = self.snake.head();
-        if hx < 0 || hy < 0 || hx >= self.w as i32 || hy >= self.h as i32 {
+        if self.out_of_bounds(hx, hy) || self.is_wall(hx, hy) {
+            self.end = Some(EndReason::Wall);
             return StepOutcome { reward: -1.0, done: true };
         }
 
         // Self-collision ends the episode with negative reward.
         if self.snake.self_collision() {
+            self.end = Some(EndReason::SelfCollision);
             return StepOutcome { reward: -1.0, done: true };
         }
 
         // Shaping: progress towards food by Manhattan distance.
-        let manh = (self.food.x as i32 - hx).abs() + (self.food.y as i32 - hy).abs();
+        let manh = self.food_distance();
         let delta = (self.last_manhattan - manh) as f32; // decrease => positive
         let alpha = 0.01f32;                             // small shaping weight
-        let delta_clamped = if delta > 1.0 { 1.0 } else if delta < -1.0 { -1.0 } else { delta };
+        let delta_clamped = delta.clamp(-1.0, 1.0);
         reward += alpha * delta_clamped;
         self.last_manhattan = manh;
 
         // Check eating.
-        if (hx as usize, hy as usize) == (self.food.x, self.food.y) {
-            self.snake.feed();
-            self.respawn_food();
-            self.steps_since_food = 0;
-            reward += 1.0; // big positive reward for food
-            return StepOutcome { reward, done: false };
+        let eaten = self.eat(hx, hy);
+        self.tick_bonus();
+        if let Some((r, cleared)) = eaten {
+            reward += r; // big positive reward for food (negative for poison)
+            if cleared {
+                // Nowhere to put new food: the snake fills the board — a win.
+                self.end = Some(EndReason::BoardCleared);
+                return StepOutcome { reward: reward + CLEAR_REWARD, done: true };
+            }
+            if r > 0.0 {
+                return StepOutcome { reward, done: false };
+            }
         }
 
         // Hunger increases if no food eaten.
@@ -163,6 +309,7 @@ impl Game {
         // Episode ends if too long without food.
         if self.steps_since_food >= self.hunger_limit {
             reward -= 0.2;
+            self.end = Some(EndReason::Starvation);
             return StepOutcome { reward, done: true };
         }
 
@@ -170,16 +317,50 @@ impl Game {
         StepOutcome { reward, done: false }
     }
 
+    /// Convert an absolute direction (e.g. from the arrow keys) into the relative action
+    /// used by `step_ai`: 0 = left, 1 = straight, 2 = right. A reversal counts as straight,
+    /// because the snake ignores it anyway.
+    pub fn relative_action(&self, want: Dir) -> u8 {
+        let cur = self.snake.dir();
+        if want == cur.turn_left() {
+            0
+        } else if want == cur.turn_right() {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Relative actions (left, straight, right) that do not end the episode on the next
+    /// step by hitting a wall or the body (hunger and poison are not considered).
+    pub fn safe_actions(&self) -> [bool; 3] {
+        let cur = self.snake.dir();
+        [cur.turn_left(), cur, cur.turn_right()].map(|d| {
+            let mut s = self.snake.clone();
+            s.apply_dir(d);
+            s.advance();
+            let (x, y) = s.head();
+            !(self.out_of_bounds(x, y) || self.is_wall(x, y) || s.self_collision())
+        })
+    }
+
     // ---------- Observation for DQN ----------
 
     /// Dimension of the observation vector.
-    /// 5 rays × (wall/body/food) + cos/sin to food + [length, hunger]
-    pub fn observation_dim(&self) -> usize { 5 * 3 + 2 + 2 }
+    /// 5 rays × (wall/body/food[/bonus/poison]) + cos/sin to food + [length, hunger]
+    pub fn observation_dim(&self) -> usize { 5 * self.ray_channels() + 2 + 2 }
+
+    // Channels per ray: the bonus/poison ones only exist with typed food, so the
+    // plain game keeps its observation (and old checkpoints stay usable).
+    fn ray_channels(&self) -> usize { if self.typed_food { 5 } else { 3 } }
 
     /// Build observation:
     /// - 5 local rays (left, left-forward, forward, right-forward, right),
-    ///   for each: normalized distances to wall/body/food;
-    /// - unit vector to food in the head's local frame (cos/sin);
+    ///   for each: normalized distances to wall/body/apple (plus bonus/poison with typed food);
+    ///   on a wrapping board rays run around the edges for up to one board length,
+    ///   so the "wall" channel reads 1.0 (no wall) and body/food are relative to that lap;
+    ///   interior level walls stop a ray exactly like the board edge;
+    /// - unit vector to the nearest apple or bonus in the head's local frame (cos/sin);
     /// - normalized snake length and hunger.
     pub fn observe(&self) -> Vec<f32> {
         let mut obs = Vec::with_capacity(self.observation_dim());
@@ -197,30 +378,18 @@ impl Game {
             ( 1, 0), // right
         ];
 
-        // Rotate local (dx,dy) into global based on current facing.
-        let rot = |dx: i32, dy: i32, d: Dir| -> (i32, i32) {
-            match d {
-                // Up means global +y is negative (screen coords), so flip dy.
-                Dir::Up    => (dx, -dy),
-                // Down flips x.
-                Dir::Down  => (-dx,  dy),
-                // Left swaps and flips both.
-                Dir::Left  => (-dy, -dx),
-                // Right swaps into (dy,dx).
-                Dir::Right => ( dy,  dx),
-            }
-        };
-
         // Upper bound for distances used for normalization.
         let max_r = (self.w.max(self.h)) as f32;
 
-        // For each ray, compute 3 distances: to wall, to body, to food.
+        // For each ray, compute distances to wall, body and each food kind.
         for (lx, ly) in rays_local {
-            let (dx, dy) = rot(lx, ly, dir);
+            let (dx, dy) = dir.rotate(lx, ly);
 
             let mut dist_wall: f32 = 0.0; // exact wall distance (cells)
             let mut dist_body: f32 = max_r;
             let mut dist_food: f32 = max_r;
+            let mut dist_bonus: f32 = max_r;
+            let mut dist_poison: f32 = max_r;
 
             // Scan forward along the ray starting one cell ahead.
             let mut step: usize = 0;
@@ -231,8 +400,20 @@ impl Game {
                 x += dx;
                 y += dy;
 
-                // Wall: first out-of-bounds cell.
-                if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+                if self.wrap {
+                    // No walls: wrap around and stop after one lap (or back at the head).
+                    x = x.rem_euclid(self.w as i32);
+                    y = y.rem_euclid(self.h as i32);
+                    if (x, y) == (hx, hy) || step as f32 >= max_r {
+                        dist_wall = step as f32;
+                        break;
+                    }
+                } else if self.out_of_bounds(x, y) {
+                    // Wall: first out-of-bounds cell.
+                    dist_wall = step as f32;
+                    break;
+                }
+                if self.is_wall(x, y) {
                     dist_wall = step as f32;
                     break;
                 }
@@ -240,9 +421,16 @@ impl Game {
                 if self.snake.occupies(x, y) && dist_body == max_r {
                     dist_body = step as f32;
                 }
-                // Food: first time we see the food on this ray.
-                if (x as usize, y as usize) == (self.food.x, self.food.y) && dist_food == max_r {
-                    dist_food = step as f32;
+                // Food: first item of each kind seen on this ray.
+                if let Some(i) = self.food_at(x, y) {
+                    let seen = match self.foods[i].kind {
+                        FoodKind::Apple => &mut dist_food,
+                        FoodKind::Bonus => &mut dist_bonus,
+                        FoodKind::Poison => &mut dist_poison,
+                    };
+                    if *seen == max_r {
+                        *seen = step as f32;
+                    }
                 }
 
                 // Safety guard to avoid infinite loops.
@@ -254,18 +442,23 @@ impl Game {
             // If body/food not seen on this ray, treat as "far" (use wall distance).
             if dist_body == max_r { dist_body = dist_wall.max(1.0); }
             if dist_food == max_r { dist_food = dist_wall.max(1.0); }
+            if dist_bonus == max_r { dist_bonus = dist_wall.max(1.0); }
+            if dist_poison == max_r { dist_poison = dist_wall.max(1.0); }
 
             // Normalize into [0,1] by wall distance (closer => smaller fraction).
             let norm = dist_wall.max(1.0);
             obs.push((dist_wall / norm).min(1.0)); // wall
             obs.push((dist_body / norm).min(1.0)); // body
             obs.push((dist_food / norm).min(1.0)); // food
+            if self.typed_food {
+                obs.push((dist_bonus / norm).min(1.0));  // bonus
+                obs.push((dist_poison / norm).min(1.0)); // poison
+            }
         }
 
         // Unit vector to food in the head's local frame -> (cos, sin).
-        let vx = self.food.x as i32 - hx;
-        let vy = self.food.y as i32 - hy;
-        let (vx_l, vy_l) = rot(vx, vy, dir);
+        let (vx, vy) = self.food_offset();
+        let (vx_l, vy_l) = dir.rotate(vx, vy);
         let len = (((vx_l * vx_l + vy_l * vy_l) as f32).sqrt()).max(1e-6);
         let cos_t = (vy_l as f32) / len; // forward component
         let sin_t = (vx_l as f32) / len; // left/right component
@@ -284,22 +477,147 @@ impl Game {
     /// External input for manual mode: set desired direction.
     pub fn set_pending_dir(&mut self, d: Dir) { self.pending_dir = d; }
 
-    /// Pick a new food cell uniformly among empty cells.
-    fn respawn_food(&mut self) {
-        loop {
-            let x = self.rng.gen_range_u32(self.w as u32) as usize;
-            let y = self.rng.gen_range_u32(self.h as u32) as usize;
-            if !self.snake.occupies(x as i32, y as i32) {
-                self.food = Food::at(x, y);
-                break;
+    /// Eat whatever lies on (x, y): returns (reward, board cleared), None if there is no food.
+    /// Eaten apples and poison are replaced; a bonus is not.
+    fn eat(&mut self, x: i32, y: i32) -> Option<(f32, bool)> {
+        let i = self.food_at(x, y)?;
+        let food = self.foods.swap_remove(i);
+        let reward = match food.kind {
+            FoodKind::Apple | FoodKind::Bonus => {
+                let points = if food.kind == FoodKind::Bonus { BONUS_POINTS } else { 1 };
+                self.snake.feed(); // grow on the next move
+                self.score += points;
+                self.steps_since_food = 0;
+                points as f32
+            }
+            FoodKind::Poison => {
+                self.snake.shrink(POISON_SHRINK);
+                POISON_REWARD
+            }
+        };
+        if food.kind != FoodKind::Bonus {
+            self.spawn_food(food.kind, 0);
+        }
+        // A new target may be closer or farther: restart the distance shaping from here.
+        self.last_manhattan = self.food_distance();
+        let cleared = !self.foods.iter().any(|f| f.kind == FoodKind::Apple);
+        Some((reward, cleared))
+    }
+
+    /// Age the bonus item and maybe spawn a new one (at most one on the board).
+    fn tick_bonus(&mut self) {
+        if self.bonus_chance <= 0.0 {
+            return;
+        }
+        for f in self.foods.iter_mut().filter(|f| f.kind == FoodKind::Bonus) {
+            f.ttl -= 1;
+        }
+        self.foods.retain(|f| f.kind != FoodKind::Bonus || f.ttl > 0);
+        let has_bonus = self.foods.iter().any(|f| f.kind == FoodKind::Bonus);
+        if !has_bonus && self.rng.next_f32() < self.bonus_chance {
+            self.spawn_food(FoodKind::Bonus, self.bonus_ttl);
+        }
+    }
+
+    /// Place a food item uniformly among empty cells (among the level's free food
+    /// spots, if it has any). Returns false (nothing placed) when no floor cell is free.
+    fn spawn_food(&mut self, kind: FoodKind, ttl: u32) -> bool {
+        let level = self.level.clone();
+        if let Some(l) = level.as_ref().filter(|l| !l.food_spots.is_empty()) {
+            let free: Vec<(usize, usize)> = l.food_spots.iter().copied()
+                .filter(|&(x, y)| self.is_free(x, y))
+                .collect();
+            if !free.is_empty() {
+                let k = self.rng.gen_range_u32(free.len() as u32) as usize;
+                self.foods.push(Food::new(free[k].0, free[k].1, kind, ttl));
+                return true;
             }
         }
+
+        let free = (0..self.h)
+            .map(|y| (0..self.w).filter(|&x| self.is_free(x, y)).count())
+            .sum::<usize>();
+        if free == 0 {
+            return false;
+        }
+        // Draw the k-th free cell in row-major order.
+        let mut k = self.rng.gen_range_u32(free as u32) as usize;
+        for y in 0..self.h {
+            for x in 0..self.w {
+                if !self.is_free(x, y) {
+                    continue;
+                }
+                if k == 0 {
+                    self.foods.push(Food::new(x, y, kind, ttl));
+                    return true;
+                }
+                k -= 1;
+            }
+        }
+        false
+    }
+
+    /// Index of the food item on (x, y), if any.
+    fn food_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.foods.iter().position(|f| (f.x as i32, f.y as i32) == (x, y))
+    }
+
+    /// Floor cell not covered by the snake or another food item.
+    fn is_free(&self, x: usize, y: usize) -> bool {
+        !self.is_wall(x as i32, y as i32)
+            && !self.snake.occupies(x as i32, y as i32)
+            && self.food_at(x as i32, y as i32).is_none()
+    }
+
+    /// Offset from the head to the nearest apple or bonus; the shortest way around on a
+    /// wrapping board. (0, 0) if there is none.
+    fn food_offset(&self) -> (i32, i32) {
+        let (hx, hy) = self.snake.head();
+        let (w, h) = (self.w as i32, self.h as i32);
+        self.foods.iter()
+            .filter(|f| f.kind != FoodKind::Poison)
+            .map(|f| {
+                let mut dx = f.x as i32 - hx;
+                let mut dy = f.y as i32 - hy;
+                if self.wrap {
+                    if dx > w / 2 { dx -= w; } else if dx < -w / 2 { dx += w; }
+                    if dy > h / 2 { dy -= h; } else if dy < -h / 2 { dy += h; }
+                }
+                (dx, dy)
+            })
+            .min_by_key(|(dx, dy)| dx.abs() + dy.abs())
+            .unwrap_or((0, 0))
+    }
+
+    /// Manhattan distance from the head to the nearest apple or bonus (wrap-aware).
+    fn food_distance(&self) -> i32 {
+        let (dx, dy) = self.food_offset();
+        dx.abs() + dy.abs()
+    }
+
+    /// True if (x, y) is off the board (always false when wrapping).
+    fn out_of_bounds(&self, x: i32, y: i32) -> bool {
+        !self.wrap && (x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32)
+    }
+
+    // Manual-mode termination.
+    fn finish(&mut self, reason: EndReason) {
+        self.done = true;
+        self.end = Some(reason);
     }
 
     // -------- getters for rendering / control --------
     pub fn width(&self) -> usize { self.w }
     pub fn height(&self) -> usize { self.h }
+    pub fn is_wrap(&self) -> bool { self.wrap }
+    /// Interior wall from the level map.
+    pub fn is_wall(&self, x: i32, y: i32) -> bool { self.level.as_ref().is_some_and(|l| l.is_wall(x, y)) }
     pub fn is_done(&self) -> bool { self.done }
-    pub fn food_pos(&self) -> (usize, usize) { (self.food.x, self.food.y) }
+    pub fn score(&self) -> u32 { self.score }
+    pub fn episode_seed(&self) -> u64 { self.episode_seed }
+    /// Name of the end reason for logs ("-" while the episode runs).
+    pub fn end_name(&self) -> &'static str { self.end.map_or("-", |r| r.name()) }
+    pub fn foods(&self) -> &[Food] { &self.foods }
     pub fn snake_segments(&self) -> Vec<(i32, i32)> { self.snake.segments_vec() }
+    pub fn snake_dir(&self) -> Dir { self.snake.dir() }
 }
@@ -0,0 +1,239 @@
+// Self-play league: the learning agent plays arena matches against frozen past
+// checkpoints sampled from a pool.
+//
+// Snake 0 is the learner; the other snakes are greedy copies of pool members.
+// After each match the learner's result against every opponent (who outlived whom,
+// score as tie-break) updates both Elo ratings. Every `snapshot_every` matches the
+// learner's current network joins the pool (the oldest member leaves when the pool
+// is full). The pool lives in `pool_dir`: one weights file per member plus
+// `league.csv` with name, Elo and games played, so a league can be resumed.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use crate::arena::Arena;
+use crate::dqn::DQNAgent;
+use crate::game::{GameConfig, StepOutcome};
+use crate::log;
+use crate::network::Net;
+use crate::utils::*;
+
+/// League settings.
+pub struct LeagueConfig {
+    pub game: GameConfig,     // board of the matches
+    pub opponents: usize,     // frozen snakes per match
+    pub snapshot_every: u64,  // matches between snapshots of the learner
+    pub pool_size: usize,     // max frozen members kept
+    pub latest_prob: f32,     // chance to pick the newest member instead of a uniform one
+    pub elo_k: f32,           // Elo K-factor
+    pub pool_dir: String,     // member weights + league.csv
+    pub seed: u64,            // opponent sampling RNG seed
+}
+
+/// A frozen checkpoint in the pool.
+struct Member {
+    name: String,
+    net: Net,
+    elo: f32,
+    games: u32,
+}
+
+/// Summary of one match for logs.
+pub struct MatchResult {
+    pub steps: u64,
+    pub learner_return: f32,
+    pub learner_score: u32,
+    pub opponents: Vec<(String, u32)>, // (member name, score)
+    pub points: f32,                   // learner's points vs. all opponents (1 win, ½ draw)
+    pub winner: Option<usize>,         // arena snake index
+}
+
+pub struct League {
+    cfg: LeagueConfig,
+    arena: Arena,
+    pub agent: DQNAgent,
+    pool: Vec<Member>,
+    pub learner_elo: f32,
+    rng: LcgRng,
+    pub matches: u64,
+    global_steps: u64,
+}
+
+impl League {
+    /// Create a league around `agent`; the pool is loaded from `pool_dir`, or seeded with
+    /// a copy of the agent if it is empty.
+    pub fn new(cfg: LeagueConfig, agent: DQNAgent) -> Self {
+        let arena = Arena::new(&cfg.game, cfg.opponents + 1);
+        let rng = LcgRng::new(cfg.seed);
+        let global_steps = agent.steps_done; // keep the ε schedule when resuming
+        let mut lg = Self { cfg, arena, agent, pool: Vec::new(), learner_elo: 1000.0, rng, matches: 0, global_steps };
+        if let Err(e) = std::fs::create_dir_all(&lg.cfg.pool_dir) {
+            log::warn(&format!("league: cannot create {}: {e}", lg.cfg.pool_dir));
+        }
+        lg.load_pool();
+        if lg.pool.is_empty() {
+            lg.snapshot();
+        }
+        lg
+    }
+
+    /// Play one match (learning online) and update ratings; snapshots on schedule.
+    pub fn play_match(&mut self) -> MatchResult {
+        let n = self.arena.num_snakes();
+        // Opponents for snakes 1..n (the same member may appear more than once).
+        let picks: Vec<usize> = (1..n).map(|_| self.pick_opponent()).collect();
+        let mut nets: Vec<Net> = picks.iter().map(|&k| self.pool[k].net.clone()).collect();
+
+        self.arena.reset();
+        let mut death_step = vec![u64::MAX; n]; // step at which each snake died
+        let mut steps = 0u64;
+        let mut ret = 0.0f32;
+        while !self.arena.is_over() {
+            let alive: Vec<bool> = (0..n).map(|i| self.arena.is_alive(i)).collect();
+            let obs0 = self.arena.observe(0);
+            let mut actions = vec![1u8; n];
+            if alive[0] {
+                actions[0] = self.agent.select_action(&obs0, &self.arena.safe_actions(0));
+            }
+            for i in (1..n).filter(|&i| alive[i]) {
+                let q = nets[i - 1].forward(&self.arena.observe(i));
+                actions[i] = if has_non_finite(&q) { 1 } else { argmax(&q) as u8 };
+            }
+            let out = self.arena.step(&actions);
+            steps += 1;
+
+            // Once the learner is dead the rest of the match is not its experience.
+            if alive[0] {
+                let StepOutcome { reward, done } = &out[0];
+                self.agent.remember(&obs0, actions[0], *reward, &self.arena.observe(0), *done);
+                ret += reward;
+                self.agent.maybe_learn();
+                self.global_steps += 1;
+                self.agent.on_step(self.global_steps);
+            }
+
+            for i in 0..n {
+                if alive[i] && !self.arena.is_alive(i) {
+                    death_step[i] = steps;
+                }
+            }
+        }
+
+        // Pairwise results of the learner against each opponent, all rated from the
+        // pre-match ratings (an opponent picked twice counts twice at its old rating).
+        let learner_elo = self.learner_elo;
+        let opp_elo: Vec<f32> = picks.iter().map(|&k| self.pool[k].elo).collect();
+        let mut points = 0.0f32;
+        for i in 1..n {
+            let s = match death_step[0].cmp(&death_step[i]) {
+                std::cmp::Ordering::Greater => 1.0,
+                std::cmp::Ordering::Less => 0.0,
+                std::cmp::Ordering::Equal => match self.arena.score(0).cmp(&self.arena.score(i)) {
+                    std::cmp::Ordering::Greater => 1.0,
+                    std::cmp::Ordering::Less => 0.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                },
+            };
+            points += s;
+            let m = &mut self.pool[picks[i - 1]];
+            let delta = self.cfg.elo_k * (s - elo_expected(learner_elo, opp_elo[i - 1]));
+            self.learner_elo += delta;
+            m.elo -= delta;
+            m.games += 1;
+        }
+
+        self.matches += 1;
+        if self.matches.is_multiple_of(self.cfg.snapshot_every.max(1)) {
+            self.snapshot();
+        }
+
+        MatchResult {
+            steps,
+            learner_return: ret,
+            learner_score: self.arena.score(0),
+            opponents: (1..n).map(|i| (self.pool_name(picks[i - 1]), self.arena.score(i))).collect(),
+            points,
+            winner: self.arena.winner(),
+        }
+    }
+
+    /// Ratings table for logs, best first.
+    pub fn table(&self) -> String {
+        let mut rows: Vec<(&str, f32)> = self.pool.iter().map(|m| (m.name.as_str(), m.elo)).collect();
+        rows.push(("learner", self.learner_elo));
+        rows.sort_by(|a, b| b.1.total_cmp(&a.1));
+        rows.iter().map(|(n, e)| format!("{n} {e:.0}")).collect::<Vec<_>>().join(", ")
+    }
+
+    // Freeze the learner into the pool, drop the oldest member if the pool is full,
+    // and write the pool to disk together with the learner's own checkpoint.
+    fn snapshot(&mut self) {
+        let name = format!("snap{:06}", self.matches);
+        let path = format!("{}/{name}.bin", self.cfg.pool_dir);
+        if let Err(e) = self.agent.online.save(&path) {
+            log::warn(&format!("league: cannot save {path}: {e}"));
+        }
+        self.pool.push(Member { name, net: self.agent.online.clone(), elo: self.learner_elo, games: 0 });
+        while self.pool.len() > self.cfg.pool_size.max(1) {
+            let old = self.pool.remove(0);
+            let _ = std::fs::remove_file(format!("{}/{}.bin", self.cfg.pool_dir, old.name));
+        }
+        if let Err(e) = self.save_table() {
+            log::warn(&format!("league: cannot write league.csv: {e}"));
+        }
+        self.agent.save_all();
+        log::info(&format!("league: snapshot after {} matches | {}", self.matches, self.table()));
+    }
+
+    // Newest member with probability latest_prob, otherwise uniform over the pool.
+    fn pick_opponent(&mut self) -> usize {
+        if self.rng.next_f32() < self.cfg.latest_prob {
+            self.pool.len() - 1
+        } else {
+            self.rng.gen_range_u32(self.pool.len() as u32) as usize
+        }
+    }
+
+    fn pool_name(&self, k: usize) -> String { self.pool[k].name.clone() }
+
+    // league.csv: "learner,elo,matches" first, then one "name,elo,games" row per member (oldest first).
+    fn save_table(&self) -> std::io::Result<()> {
+        let mut f = File::create(format!("{}/league.csv", self.cfg.pool_dir))?;
+        writeln!(f, "learner,{},{}", self.learner_elo, self.matches)?;
+        for m in &self.pool {
+            writeln!(f, "{},{},{}", m.name, m.elo, m.games)?;
+        }
+        Ok(())
+    }
+
+    // Restore ratings and member weights written by save_table/snapshot.
+    fn load_pool(&mut self) {
+        let Ok(f) = File::open(format!("{}/league.csv", self.cfg.pool_dir)) else { return };
+        for line in BufReader::new(f).lines().map_while(Result::ok) {
+            let fields: Vec<&str> = line.split(',').collect();
+            if fields.len() != 3 {
+                continue;
+            }
+            let (Ok(elo), Ok(games)) = (fields[1].parse::<f32>(), fields[2].parse::<u64>()) else { continue };
+            if fields[0] == "learner" {
+                // Continue the match count so new snapshot names stay unique.
+                self.learner_elo = elo;
+                self.matches = games;
+                continue;
+            }
+            let mut net = self.agent.online.clone();
+            let path = format!("{}/{}.bin", self.cfg.pool_dir, fields[0]);
+            match net.load(&path) {
+                Ok(()) => self.pool.push(Member { name: fields[0].to_string(), net, elo, games: games as u32 }),
+                Err(e) => log::warn(&format!("league: skipping {path}: {e}")),
+            }
+        }
+        if !self.pool.is_empty() {
+            log::info(&format!("league: loaded {} members | {}", self.pool.len(), self.table()));
+        }
+    }
+}
+
+// Expected score of a player rated `a` against one rated `b`.
+fn elo_expected(a: f32, b: f32) -> f32 {
+    1.0 / (1.0 + 10f32.powf((b - a) / 400.0))
+}
@@ -0,0 +1,151 @@
+// Level maps loaded from text files.
+//
+// Format: one line per board row, all rows the same width.
+//   '#'        wall
+//   '.' or ' ' floor
+//   'S'        snake start: the head, facing right; the body extends to the left,
+//              so the cells left of 'S' must be floor for the starting length
+//              (without 'S' the same holds for the default spawn right of the centre)
+//   'F'        fixed food spot (floor); when a level has spots, food only appears
+//              on them (any free cell is used if every spot is covered)
+// Lines starting with ';' are comments. Spaces are cells, so a row whose right edge is
+// floor keeps its trailing spaces; only empty lines before the first and after the last
+// row are ignored (as is the '\r' of CRLF files).
+
+use std::sync::Arc;
+
+pub struct Level {
+    pub name: String,
+    pub w: usize,
+    pub h: usize,
+    walls: Vec<bool>,                    // row-major, w * h
+    pub start: Option<(i32, i32)>,       // head cell of the starting snake
+    pub food_spots: Vec<(usize, usize)>, // allowed food cells (empty = anywhere)
+}
+
+impl Level {
+    /// Parse a level from its text; the spawn must have room for a snake of `start_len`.
+    pub fn parse(name: &str, text: &str, start_len: usize) -> Result<Level, String> {
+        let mut rows: Vec<&str> = text
+            .lines()
+            .filter(|l| !l.starts_with(';'))
+            .map(|l| l.strip_suffix('\r').unwrap_or(l))
+            .collect();
+        while rows.last().is_some_and(|l| l.is_empty()) {
+            rows.pop();
+        }
+        let first = rows.iter().position(|l| !l.is_empty()).unwrap_or(rows.len());
+        rows.drain(..first);
+        if rows.is_empty() {
+            return Err(format!("{name}: empty level"));
+        }
+        let w = rows[0].chars().count();
+        let h = rows.len();
+        if w < 4 || h < 4 {
+            return Err(format!("{name}: level {w}x{h} is too small"));
+        }
+
+        let mut walls = vec![false; w * h];
+        let mut start = None;
+        let mut food_spots = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != w {
+                return Err(format!("{name}: row {} has width {}, expected {w}", y + 1, row.chars().count()));
+            }
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '#' => walls[y * w + x] = true,
+                    '.' | ' ' => {}
+                    'S' => {
+                        if start.is_some() {
+                            return Err(format!("{name}: more than one 'S'"));
+                        }
+                        start = Some((x as i32, y as i32));
+                    }
+                    'F' => food_spots.push((x, y)),
+                    _ => return Err(format!("{name}: unknown cell '{c}' at row {}, column {}", y + 1, x + 1)),
+                }
+            }
+        }
+        let level = Level { name: name.to_string(), w, h, walls, start, food_spots };
+        level.check_spawn(start_len)?;
+        Ok(level)
+    }
+
+    /// Load and parse a level file.
+    pub fn load(path: &str, start_len: usize) -> Result<Arc<Level>, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Level::parse(path, &text, start_len).map(Arc::new)
+    }
+
+    /// Head cell of the starting snake: 'S', or right of the centre like on an open board.
+    pub fn spawn_head(&self) -> (i32, i32) {
+        self.start.unwrap_or(((self.w / 2 + 1) as i32, (self.h / 2) as i32))
+    }
+
+    // The head and the body cells left of it (as many as fit on the board, like
+    // `Game::from_config` clamps the length) must be floor.
+    fn check_spawn(&self, start_len: usize) -> Result<(), String> {
+        let (hx, hy) = self.spawn_head();
+        let len = start_len.clamp(1, hx as usize + 1) as i32;
+        let what = if self.start.is_some() { "'S'" } else { "the default spawn (add an 'S')" };
+        match (hx + 1 - len..=hx).find(|&x| self.is_wall(x, hy)) {
+            Some(x) => Err(format!(
+                "{}: wall at row {}, column {} blocks {what} for a snake of length {len}",
+                self.name, hy + 1, x + 1,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// True for interior wall cells (false off the map).
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+            return false;
+        }
+        self.walls[y as usize * self.w + x as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cells() {
+        let text = "; a comment\r\n\r\n######\r\n#S..F#\r\n#  # #\r\n######\r\n\r\n";
+        let l = Level::parse("t", text, 1).unwrap();
+        assert_eq!((l.w, l.h), (6, 4));
+        assert_eq!(l.start, Some((1, 1)));
+        assert_eq!(l.food_spots, vec![(4, 1)]);
+        assert!(l.is_wall(0, 0) && l.is_wall(3, 2));
+        assert!(!l.is_wall(1, 2) && !l.is_wall(4, 2) && !l.is_wall(-1, 0) && !l.is_wall(6, 0));
+    }
+
+    #[test]
+    fn spaces_are_floor() {
+        // A right edge of floor and a row of nothing but floor keep their width.
+        let l = Level::parse("t", "#   \n    \n  S \n#   \n", 3).unwrap();
+        assert_eq!((l.w, l.h), (4, 4));
+        assert!(l.is_wall(0, 3) && !l.is_wall(3, 1));
+    }
+
+    #[test]
+    fn reject_bad_levels() {
+        let bad = [
+            ("", "empty"),
+            ("....\n....\n", "too small"),
+            ("....\n...\n....\n....\n", "width"),
+            ("S...\n....\n...S\n....\n", "more than one"),
+            ("....\n..x.\n....\n....\n", "unknown cell"),
+            ("....\n#.S.\n....\n....\n", "blocks 'S'"),
+            ("....\n....\n##..\n....\n", "default spawn"),
+        ];
+        for (text, err) in bad {
+            let e = Level::parse("t", text, 3).err().unwrap_or_else(|| panic!("{text:?} should be rejected"));
+            assert!(e.contains(err), "{text:?}: {e}");
+        }
+        // A shorter snake fits next to the wall.
+        assert!(Level::parse("t", "....\n#.S.\n....\n....\n", 2).is_ok());
+    }
+}
@@ -7,33 +7,193 @@ mod game;        // Game logic & observation.
 mod event_loop;  // Window/render for manual/AI preview.
 mod network;     // Neural net.
 mod dqn;         // DQN agent.
+mod mcts;        // Tree search over cloned game states.
+mod evolve;      // Evolution strategies for Net weights.
+mod demo;        // Demonstration datasets.
+mod curriculum;  // Board-size / start-length curriculum.
+mod level;       // Level maps with interior walls.
+mod encoder;     // Observation encoders (rays, grid, egocentric crop).
+mod arena;       // Several snakes on one board.
+mod league;      // Self-play against a pool of frozen checkpoints.
+mod schedule;    // Learning-rate / ε schedules.
+mod explore;     // Exploration strategies (ε-greedy, softmax, per-episode ε).
+mod optim;       // Optimizers (SGD, RMSProp, Adam, AdamW) and their checkpoints.
 
 use std::env;
-use crate::game::{Game, StepOutcome};
-use crate::dqn::{DQNAgent, AgentConfig};
+use crate::game::{Game, GameConfig, StepOutcome};
+use crate::arena::{Arena, Controller};
+use crate::curriculum::Curriculum;
+use crate::encoder::{FrameStack, ObsEncoder, ObsShape};
+use crate::dqn::{DQNAgent, AgentConfig, Rails, TargetUpdate};
+use crate::mcts::{MctsAgent, MctsConfig};
+use crate::evolve::{EsConfig, EsTrainer};
+use crate::league::{League, LeagueConfig};
+use crate::network::{LayerSpec, Net};
+use crate::schedule::Schedule;
+use crate::explore::Exploration;
+use crate::optim::OptimSpec;
+use crate::utils::LcgRng;
 
 fn main() {
     // Parse flags after the program name.
     let args: Vec<String> = env::args().skip(1).collect();
 
+    // Value of a flag (see `arg_value`); a malformed value is fatal.
+    macro_rules! flag {
+        ($flag:expr $(, $t:ty)?) => {
+            match arg_value$(::<$t>)?(&args, $flag) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("fatal: {e}");
+                    return;
+                }
+            }
+        };
+    }
+
     // Select mode.
     let mode = if args.contains(&"--best".to_string()) {
         "best"
     } else if args.contains(&"--train".to_string()) {
         "train"
+    } else if args.contains(&"--mcts".to_string()) {
+        "mcts"
+    } else if args.contains(&"--evolve".to_string()) {
+        "evolve"
+    } else if args.contains(&"--league".to_string()) {
+        "league"
+    } else if args.contains(&"--arena".to_string()) {
+        "arena"
+    } else if args.contains(&"--pretrain".to_string()) {
+        "pretrain"
     } else {
         "run"
     };
 
+    // The arena (and the league on top of it) plays on an open board with plain apples.
+    if matches!(mode, "arena" | "league") {
+        let unsupported = ["--level", "--wrap", "--bonus", "--bonus-ttl", "--poison"];
+        if let Some(flag) = unsupported.iter().find(|f| args.contains(&f.to_string())) {
+            eprintln!("fatal: {flag} is not supported with --{mode} (only --apples changes the arena's food)");
+            return;
+        }
+        // Arena snakes see their own ray encoding with an opponent channel (see `Arena::observe`).
+        if args.contains(&"--obs".to_string()) {
+            eprintln!("fatal: --obs is not supported with --{mode} (arena agents use the built-in ray observation)");
+            return;
+        }
+    }
+
     // Board size (tweak if needed).
     let w = 24usize;
     let h = 16usize;
 
+    // `--seed N`: seed of the first episode (results.csv lists every episode's seed,
+    // so any episode can be replayed with e.g. `--best --seed <seed>`).
+    let mut game_cfg = GameConfig::new(w, h);
+    game_cfg.seed = flag!("--seed").unwrap_or(game::DEFAULT_SEED);
+    // `--wrap`: toroidal board (edges are not lethal).
+    game_cfg.wrap = args.contains(&"--wrap".to_string());
+    // `--apples N`, `--bonus P [--bonus-ttl T]`, `--poison N`: several apples, a bonus item
+    // that appears with chance P per step and lasts T steps, N poison items.
+    game_cfg.apples = flag!("--apples").unwrap_or(game_cfg.apples);
+    game_cfg.bonus_chance = flag!("--bonus").unwrap_or(game_cfg.bonus_chance);
+    game_cfg.bonus_ttl = flag!("--bonus-ttl").unwrap_or(game_cfg.bonus_ttl);
+    game_cfg.poison = flag!("--poison").unwrap_or(game_cfg.poison);
+    // `--level <file>`: play on a level map (see levels/); its size replaces the board size.
+    if let Some(path) = flag!("--level", String) {
+        match level::Level::load(&path, game_cfg.start_len) {
+            Ok(l) => {
+                log::info(&format!("level {} ({}x{}, {} food spots)", l.name, l.w, l.h, l.food_spots.len()));
+                game_cfg.level = Some(l);
+            }
+            Err(e) => {
+                eprintln!("fatal: cannot load level {e}");
+                return;
+            }
+        }
+    }
+
+    // `--obs rays|grid[+age]|ego[:R][+age]`: observation encoder of single-snake agents.
+    let encoder = match ObsEncoder::parse(&flag!("--obs", String).unwrap_or_else(|| "rays".to_string())) {
+        Ok(enc) => enc,
+        Err(e) => {
+            eprintln!("fatal: {e}");
+            return;
+        }
+    };
+    // `--stack K`: the agent sees the last K observations (frame stacking), --train and --best only.
+    let frames = FrameStack::new(encoder, flag!("--stack").unwrap_or(1));
+    if frames.k > 1 && !matches!(mode, "train" | "best") {
+        eprintln!("fatal: --stack is only supported by --train and --best");
+        return;
+    }
+
+    // `--net <spec>`: hidden layers of the network, e.g. `128@he,leaky,128@he,leaky,out@zero`
+    // or `conv:16:3:1:1,relu,flatten,64,gelu` (conv blocks need a grid-like --obs); see
+    // `LayerSpec::parse_list` for activations and initializers.
+    let arch = match LayerSpec::parse_list(&flag!("--net", String).unwrap_or_else(|| "64,relu,64,relu".to_string())) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("fatal: {e}");
+            return;
+        }
+    };
+    // A GRU already remembers past observations, and the sequence replay stores whole frames.
+    if frames.k > 1 && LayerSpec::is_recurrent(&arch) {
+        eprintln!("fatal: --stack cannot be combined with a gru layer in --net");
+        return;
+    }
+    if let Err(e) = Net::check_spec(frames.shape(&Game::from_config(&game_cfg)).chw(), &arch) {
+        eprintln!("fatal: --net {e}");
+        return;
+    }
+    // `--lr <schedule>` / `--eps <schedule>`: DQN learning rate and ε over environment steps,
+    // e.g. `--lr cos:2.5e-4:1e-5:1000:500000 --eps exp:1:0.02:200000` (see `schedule`).
+    // `--explore eps|softmax:<schedule>|episode:LO:HI[+safe]`: exploration strategy (see `explore`),
+    // also used by the --best / arena previews.
+    // `--optim sgd[:M]|nesterov[:M]|rmsprop[:A[:E]]|adam[...]|adamw[...]`: optimizer (see `optim`);
+    // its state is kept in its own file next to the weights.
+    // `--grad-clip X --param-clip X --reward-clip X --target-clip X` (0 turns a rail off),
+    // `--nan-halt N [--nan-dump PREFIX]`: stop after N NaN/Inf events and dump the nets,
+    // optimizer and agent state to PREFIX_*.bin (see `dqn::Rails`).
+    // `--target-update soft:TAU|hard:K`: Polyak averaging or a copy every K updates;
+    // `--vanilla-dqn`: max_a Q_target(s', a) targets instead of Double-DQN.
+    let overrides = match AgentOverrides::from_args(&args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("fatal: {e}");
+            return;
+        }
+    };
+
+    // A `gru:H` layer makes the DQN recurrent (DRQN); only --train and --best support it.
+    if LayerSpec::is_recurrent(&arch) && matches!(mode, "evolve" | "pretrain") {
+        eprintln!("fatal: --net with a gru layer is not supported by --{mode}");
+        return;
+    }
+
     match mode {
-        // Manual play with arrows (no learning).
+        // Manual play with arrows (no learning). `--record <file>` saves the play as demonstrations.
         "run" => {
-            let game = Game::new(w, h);
-            if let Err(e) = event_loop::run_manual(game) {
+            let game = Game::from_config(&game_cfg);
+            let recorder = match flag!("--record", String) {
+                Some(path) => match demo::DemoWriter::open(&path, encoder.dim(&game)) {
+                    Ok(rec) => {
+                        log::info(&format!("recording demonstrations to {path} ({} steps already)", rec.steps));
+                        if rec.dropped > 0 {
+                            log::warn(&format!("{path}: dropped a truncated last record ({} bytes)", rec.dropped));
+                        }
+                        Some(rec)
+                    }
+                    Err(e) => {
+                        eprintln!("fatal: cannot open {path}: {e}");
+                        return;
+                    }
+                },
+                None => None,
+            };
+            if let Err(e) = event_loop::run_manual(game, recorder, encoder) {
                 eprintln!("fatal: {e}");
             }
         }
@@ -41,53 +201,95 @@ fn main() {
         // Preview the trained/best model in a window (no learning).
         "best" => {
             // Create game and agent. We set eps_start = eps_end ~ 0.05 for near-greedy play.
-            let mut game = Game::new(w, h);
+            let game = Game::from_config(&game_cfg);
             let cfg = AgentConfig {
-                obs_dim: game.observation_dim(),
+                obs_dim: frames.dim(&game),
                 act_dim: 3,
-                hidden: 64,
                 buffer_capacity: 100_000,
                 batch_size: 128,
                 gamma: 0.99,
-                lr: 2.5e-4,            // safer LR
-                eps_start: 0.05,
-                eps_end: 0.05,
-                eps_decay_steps: 1,
-                tau: 0.005,
+                lr: Schedule::Constant(2.5e-4), // safer LR
+                eps: Schedule::Constant(0.05),
+                target_update: TargetUpdate::Soft { tau: 0.005 },
+                double_dqn: true,
                 learn_start: 10_000,
                 updates_per_step: 1,
                 seed: 42,
+                demo_margin: 0.8,
+                demo_lambda: 1.0,
+                weights_path: "weights.bin".to_string(),
+                state_path: "agent_state.bin".to_string(),
+                optim_path: "optim_state.bin".to_string(),
+                obs_shape: frames.shape(&game).chw(),
+                arch: arch.clone(),
+                seq_len: 16,
+                burn_in: 8,
+                frame_stack: frames.k,
+                explore: Exploration::default(),
+                optim: OptimSpec::default(),
+                rails: Rails::default(),
             };
-            let mut agent = DQNAgent::new(cfg);
+            let mut agent = DQNAgent::new(overrides.apply(cfg, &game_cfg));
             // Freeze epsilon to greedyish.
             agent.on_step(u64::MAX / 2);
 
             // Open a window where the agent acts; no training inside.
-            if let Err(e) = event_loop::run_ai_preview(game, agent) {
+            if let Err(e) = event_loop::run_ai_preview(game, agent, frames) {
                 eprintln!("fatal: {e}");
             }
         }
 
         // Headless training loop (fast as possible).
         "train" => {
-            let mut game = Game::new(w, h);
-            let cfg = AgentConfig {
-                obs_dim: game.observation_dim(),
-                act_dim: 3,
-                hidden: 64,
-                buffer_capacity: 100_000,
-                batch_size: 128,
-                gamma: 0.99,
-                lr: 2.5e-4,            // ↓ safer LR
-                eps_start: 1.0,
-                eps_end: 0.05,
-                eps_decay_steps: 100_000,
-                tau: 0.005,
-                learn_start: 5_000,
-                updates_per_step: 1,   // ↓ fewer updates per step for stability
-                seed: 1234567,
+            // `--curriculum [--stages WxH:len:thr,...] [--eval-episodes N]`: start small and
+            // promote when N greedy evaluation games (every 100 episodes) reach the threshold.
+            let mut curriculum = if args.contains(&"--curriculum".to_string()) {
+                let stages = match flag!("--stages", String) {
+                    Some(spec) => match Curriculum::parse_stages(&spec) {
+                        Ok(st) => st,
+                        Err(e) => {
+                            eprintln!("fatal: {e}");
+                            return;
+                        }
+                    },
+                    None => Curriculum::default_stages(),
+                };
+                if encoder.board_sized() {
+                    eprintln!("fatal: --curriculum changes the board size; use --obs rays or ego");
+                    return;
+                }
+                if game_cfg.level.is_some() {
+                    eprintln!("fatal: --curriculum changes the board size and start length; it cannot be used with --level");
+                    return;
+                }
+                Some(Curriculum::new(stages, 100, flag!("--eval-episodes").unwrap_or(20)))
+            } else {
+                None
             };
+            let mut game = match curriculum.as_ref() {
+                Some(c) => {
+                    log::info(&format!("curriculum {}", c.describe()));
+                    Game::from_config(&c.game_config(&game_cfg))
+                }
+                None => Game::from_config(&game_cfg),
+            };
+            log::info(&format!("observation {}", frames.describe(&game)));
+            // `--seq-len L --burn-in B`: replayed episode chunks of a recurrent net.
+            let mut cfg = overrides.apply(single_config(frames.shape(&game), &arch), &game_cfg);
+            cfg.frame_stack = frames.k;
+            cfg.seq_len = flag!("--seq-len").unwrap_or(cfg.seq_len);
+            cfg.burn_in = flag!("--burn-in").unwrap_or(cfg.burn_in);
             let mut agent = DQNAgent::new(cfg);
+            log::info(&format!("network {}", agent.online.describe()));
+
+            // `--demos a.bin,b.bin`: keep demonstrations in replay with the DQfD margin loss.
+            if let Some(list) = flag!("--demos", String) {
+                if agent.online.is_recurrent() || frames.k > 1 {
+                    eprintln!("fatal: --demos is not supported with a gru layer in --net or --stack");
+                    return;
+                }
+                agent.preload_demonstrations(&load_demo_files(&list, encoder.dim(&game)));
+            }
 
             // Episode counters.
             let mut episode_idx: u64 = 0;
@@ -95,14 +297,18 @@ fn main() {
             let mut episode_steps: u64 = 0;
             let mut global_steps: u64 = 0;
 
+            let mut frames = frames;
+            let mut obs = frames.observe(&game);
             loop {
-                let obs = game.observe();
-                let a = agent.select_action(&obs);
+                let a = agent.select_action(&obs, &game.safe_actions());
                 let StepOutcome { reward, done } = game.step_ai(a);
-                let next_obs = game.observe();
+                let next_obs = frames.observe(&game);
 
                 agent.remember(&obs, a, reward, &next_obs, done);
                 agent.maybe_learn();
+                if agent.halted() {
+                    break;
+                }
 
                 episode_return += reward;
                 episode_steps += 1;
@@ -110,34 +316,565 @@ fn main() {
                 agent.on_step(global_steps);
 
                 if done {
+                    let stage = curriculum.as_ref().map_or(0, |c| c.stage());
                     let _ = db::append_episode_result(
                         "results.csv",
                         episode_idx,
                         episode_return,
                         episode_steps,
+                        stage,
+                        game.episode_seed(),
                     );
                     log::info(&format!(
-                        "EP {:5} | ret {:7.3} | steps {:4} | eps {:.3} | loss {:.4} | buffer {}",
+                        "EP {:5} | ret {:7.3} | steps {:4} | end {:7} | eps {:.3} | loss {:.4} | buffer {} | seed {}",
                         episode_idx,
                         episode_return,
                         episode_steps,
+                        game.end_name(),
                         agent.current_epsilon(),
                         agent.last_loss,        // public field
                         agent.replay_len(),
+                        game.episode_seed(),
                     ));
                     episode_idx += 1;
                     episode_return = 0.0;
                     episode_steps = 0;
-                    game.reset();
+
+                    // Curriculum: periodic greedy evaluations; a promotion swaps in a game
+                    // for the next stage.
+                    let mut promoted = false;
+                    if let Some(c) = curriculum.as_mut() {
+                        let due = c.record(game.score());
+                        log::scalar(global_steps, "curriculum_score", c.rolling_score());
+                        if due {
+                            let eval = c.evaluate(&agent.online, &frames, &game_cfg);
+                            log::scalar(global_steps, "curriculum_eval", eval);
+                            log::info(&format!("curriculum eval {} | greedy score {eval:.2}", c.describe()));
+                            promoted = c.promote_on(eval);
+                        }
+                    }
+                    if let (true, Some(c)) = (promoted, curriculum.as_ref()) {
+                        log::info(&format!("curriculum promoted to {} at episode {}", c.describe(), episode_idx));
+                        let mut cfg = c.game_config(&game_cfg);
+                        cfg.seed = utils::mix_seed(game.episode_seed());
+                        game = Game::from_config(&cfg);
+                    } else {
+                        game.reset();
+                    }
                 }
+                obs = if done {
+                    frames.reset();
+                    frames.observe(&game)
+                } else {
+                    next_obs
+                };
 
                 // Periodic save.
-                if global_steps % 10_000 == 0 {
+                if global_steps.is_multiple_of(10_000) {
                     agent.save_all();
                 }
             }
         }
 
+        // Multi-snake arena. `--arena human,agent,bot,...` gives one controller per snake.
+        // Agent snakes share one DQN agent stored in arena_weights.bin (the arena observation
+        // has an extra opponent channel, so single-snake weights do not fit). With a human
+        // snake or `--preview` the arena opens in a window; otherwise agent snakes train
+        // headless against the others.
+        "arena" => {
+            let controllers = match Controller::parse_list(&flag!("--arena", String).unwrap_or_default()) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("fatal: {e}");
+                    return;
+                }
+            };
+            if controllers.len() > game_cfg.h / 2 {
+                eprintln!("fatal: a {}x{} board fits at most {} snakes", game_cfg.w, game_cfg.h, game_cfg.h / 2);
+                return;
+            }
+            let mut arena = Arena::new(&game_cfg, controllers.len());
+            let has_agent = controllers.contains(&Controller::Agent);
+            let acfg = match arena_config(arena.observation_dim(), &arch) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("fatal: --net {e}");
+                    return;
+                }
+            };
+
+            if controllers.contains(&Controller::Human) || args.contains(&"--preview".to_string()) {
+                // Near-greedy agent, no learning.
+                let agent = has_agent.then(|| {
+                    let mut agent = DQNAgent::new(overrides.apply(AgentConfig { eps: Schedule::Constant(0.05), ..acfg }, &game_cfg));
+                    agent.on_step(u64::MAX / 2);
+                    agent
+                });
+                if let Err(e) = event_loop::run_arena(arena, controllers, agent) {
+                    eprintln!("fatal: {e}");
+                }
+                return;
+            }
+
+            let mut agent = has_agent.then(|| DQNAgent::new(overrides.apply(acfg, &game_cfg)));
+            let names: Vec<String> = controllers.iter().enumerate().map(|(i, c)| format!("{}#{i}", c.name())).collect();
+            let mut episode_idx: u64 = 0;
+            let mut episode_steps: u64 = 0;
+            let mut global_steps: u64 = 0;
+            loop {
+                // Observe and act for every living snake.
+                let alive: Vec<bool> = (0..controllers.len()).map(|i| arena.is_alive(i)).collect();
+                let obs: Vec<Vec<f32>> = (0..controllers.len())
+                    .map(|i| if alive[i] && controllers[i] == Controller::Agent { arena.observe(i) } else { Vec::new() })
+                    .collect();
+                let actions: Vec<u8> = (0..controllers.len())
+                    .map(|i| match (controllers[i], agent.as_mut()) {
+                        _ if !alive[i] => 1,
+                        (Controller::Agent, Some(ag)) => ag.select_action(&obs[i], &arena.safe_actions(i)),
+                        _ => arena.bot_action(i),
+                    })
+                    .collect();
+                let outcomes = arena.step(&actions);
+
+                // Each agent snake contributes its own transition.
+                if let Some(ag) = agent.as_mut() {
+                    for i in (0..controllers.len()).filter(|&i| alive[i] && controllers[i] == Controller::Agent) {
+                        let StepOutcome { reward, done } = outcomes[i];
+                        ag.remember(&obs[i], actions[i], reward, &arena.observe(i), done);
+                    }
+                    ag.maybe_learn();
+                    if ag.halted() {
+                        break;
+                    }
+                }
+                episode_steps += 1;
+                global_steps += 1;
+                if let Some(ag) = agent.as_mut() {
+                    ag.on_step(global_steps);
+                }
+
+                if arena.is_over() {
+                    let results: Vec<String> = (0..controllers.len())
+                        .map(|i| format!("{} {} ({})", names[i], arena.score(i), arena.end_name(i)))
+                        .collect();
+                    log::info(&format!(
+                        "ARENA EP {:5} | steps {:4} | {} | winner {} | seed {}",
+                        episode_idx,
+                        episode_steps,
+                        results.join(", "),
+                        arena.winner().map_or("-", |w| names[w].as_str()),
+                        arena.episode_seed(),
+                    ));
+                    episode_idx += 1;
+                    episode_steps = 0;
+                    arena.reset();
+                }
+
+                if global_steps.is_multiple_of(10_000) {
+                    if let Some(ag) = agent.as_ref() {
+                        ag.save_all();
+                    }
+                }
+            }
+        }
+
+        // Self-play league in the arena: `--opponents N` frozen snakes per match, a snapshot
+        // every `--snapshot-every M` matches, at most `--pool K` members (kept in league/).
+        // The learner shares arena_weights.bin with `--arena`.
+        "league" => {
+            let lcfg = LeagueConfig {
+                game: game_cfg.clone(),
+                opponents: flag!("--opponents").unwrap_or(1),
+                snapshot_every: flag!("--snapshot-every").unwrap_or(200),
+                pool_size: flag!("--pool").unwrap_or(10),
+                latest_prob: 0.5,
+                elo_k: 16.0,
+                pool_dir: "league".to_string(),
+                seed: 99,
+            };
+            if lcfg.opponents + 1 > game_cfg.h / 2 {
+                eprintln!("fatal: a {}x{} board fits at most {} snakes", game_cfg.w, game_cfg.h, game_cfg.h / 2);
+                return;
+            }
+            let obs_dim = Arena::new(&game_cfg, 1).observation_dim();
+            let acfg = match arena_config(obs_dim, &arch) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("fatal: --net {e}");
+                    return;
+                }
+            };
+            let mut league = League::new(lcfg, DQNAgent::new(overrides.apply(acfg, &game_cfg)));
+            loop {
+                let r = league.play_match();
+                let opps: Vec<String> = r.opponents.iter().map(|(name, sc)| format!("{name} {sc}")).collect();
+                log::info(&format!(
+                    "LEAGUE {:6} | steps {:4} | ret {:7.3} | score {:3} | vs {} | points {:.1} | winner {} | elo {:.0} | eps {:.3}",
+                    league.matches,
+                    r.steps,
+                    r.learner_return,
+                    r.learner_score,
+                    opps.join(", "),
+                    r.points,
+                    r.winner.map_or("-".to_string(), |w| if w == 0 { "learner".to_string() } else { format!("#{w}") }),
+                    league.learner_elo,
+                    league.agent.current_epsilon(),
+                ));
+                if league.agent.halted() {
+                    break;
+                }
+            }
+        }
+
+        // Headless MCTS play. `--mcts-net` uses weights.bin as prior/value estimator,
+        // `--distill` additionally trains that net towards the search Q-values,
+        // `--record <file>` saves the bot's play as demonstrations.
+        "mcts" => {
+            let mut game = Game::from_config(&game_cfg);
+            let distill = args.contains(&"--distill".to_string());
+            let use_net = distill || args.contains(&"--mcts-net".to_string());
+            if use_net && LayerSpec::is_recurrent(&arch) {
+                eprintln!("fatal: --mcts-net/--distill need a feed-forward --net (no gru layer)");
+                return;
+            }
+
+            let net = if use_net {
+                let mut net = Net::from_spec(encoder.shape(&game).chw(), &arch, 3, LcgRng::new(42));
+                if net.load("weights.bin").is_err() {
+                    log::warn("mcts: weights.bin not loaded — using a fresh network");
+                }
+                Some(net)
+            } else {
+                None
+            };
+            // With a net the search discounts like the DQN that trained it, so distilled
+            // Q-values are on the net's scale.
+            let dqn_cfg = use_net.then(|| overrides.apply(single_config(encoder.shape(&game), &arch), &game_cfg));
+            let mcts_cfg = MctsConfig {
+                simulations: flag!("--sims").unwrap_or(200),
+                c_puct: 1.5,
+                gamma: dqn_cfg.as_ref().map_or(0.95, |c| c.gamma),
+                rollout_depth: 30,
+                prior_temp: 0.5,
+                seed: 7,
+                encoder,
+            };
+            let mut mcts = MctsAgent::new(mcts_cfg, net);
+            let mut agent = dqn_cfg.filter(|_| distill).map(DQNAgent::new);
+            let mut recorder = match flag!("--record", String) {
+                Some(path) => match demo::DemoWriter::open(&path, encoder.dim(&game)) {
+                    Ok(rec) => {
+                        if rec.dropped > 0 {
+                            log::warn(&format!("{path}: dropped a truncated last record ({} bytes)", rec.dropped));
+                        }
+                        Some(rec)
+                    }
+                    Err(e) => {
+                        eprintln!("fatal: cannot open {path}: {e}");
+                        return;
+                    }
+                },
+                None => None,
+            };
+
+            let mut batch: Vec<(Vec<f32>, Vec<Option<f32>>)> = Vec::new();
+            let mut fits: u64 = 0;
+            let mut episode_idx: u64 = 0;
+            let mut episode_return: f32 = 0.0;
+            let mut episode_steps: u64 = 0;
+
+            loop {
+                let res = mcts.search(&game);
+                if let Some(agent) = agent.as_mut() {
+                    // Root Q estimates are an improved target for the current state;
+                    // actions the search never tried have no estimate and are not fitted.
+                    let targets = res.q.iter().zip(res.visits).map(|(&q, v)| (v > 0).then_some(q)).collect();
+                    batch.push((encoder.encode(&game), targets));
+                    if batch.len() >= 64 {
+                        agent.fit_q_targets(&batch);
+                        if agent.halted() {
+                            break;
+                        }
+                        batch.clear();
+                        fits += 1;
+                        if let Some(net) = mcts.net_mut() { net.copy_from(&agent.online); }
+                        if fits.is_multiple_of(50) { agent.save_all(); }
+                    }
+                }
+
+                let obs = if recorder.is_some() { encoder.encode(&game) } else { Vec::new() };
+                let StepOutcome { reward, done } = game.step_ai(res.action);
+                if let Some(rec) = recorder.as_mut() {
+                    let step = demo::DemoStep { obs, action: res.action, reward, next_obs: encoder.encode(&game), done };
+                    if let Err(e) = rec.push(&step) {
+                        log::error(&format!("cannot write demonstration: {e}"));
+                    }
+                }
+                episode_return += reward;
+                episode_steps += 1;
+
+                if done {
+                    let _ = db::append_episode_result(
+                        "mcts_results.csv",
+                        episode_idx,
+                        episode_return,
+                        episode_steps,
+                        0,
+                        game.episode_seed(),
+                    );
+                    log::info(&format!(
+                        "MCTS EP {:5} | ret {:7.3} | steps {:4} | score {:3} | end {:7} | seed {}",
+                        episode_idx,
+                        episode_return,
+                        episode_steps,
+                        game.score(),
+                        game.end_name(),
+                        game.episode_seed(),
+                    ));
+                    episode_idx += 1;
+                    episode_return = 0.0;
+                    episode_steps = 0;
+                    game.reset();
+                }
+            }
+        }
+
+        // Gradient-free training with evolution strategies; checkpoints are weights.bin-compatible.
+        // `--pairs N` antithetic pairs per generation (population 2N), `--sigma S` noise scale,
+        // `--es-lr LR` step size, `--games K` games per fitness evaluation.
+        "evolve" => {
+            let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+            let pairs: usize = flag!("--pairs").unwrap_or(32);
+            let sigma: f32 = flag!("--sigma").unwrap_or(0.05);
+            let games: usize = flag!("--games").unwrap_or(5);
+            if pairs == 0 || games == 0 || !(sigma > 0.0 && sigma.is_finite()) {
+                eprintln!("fatal: --evolve needs --pairs >= 1, --games >= 1 and a positive --sigma");
+                return;
+            }
+            let cfg = EsConfig {
+                game: game_cfg.clone(),
+                encoder,
+                arch: arch.clone(),
+                pairs,
+                sigma,
+                lr: flag!("--es-lr").unwrap_or(0.03),
+                games,
+                max_steps: 5_000,
+                threads: flag!("--threads").unwrap_or(threads),
+                seed: 2024,
+                out_path: "weights.bin".to_string(),
+            };
+            let mut es = EsTrainer::new(cfg);
+            loop {
+                let (mean, center) = es.step();
+                log::info(&format!(
+                    "ES GEN {:5} | pop mean {:7.3} | center {:7.3}",
+                    es.generation, mean, center,
+                ));
+                log::scalar(es.generation, "es_pop_mean", mean);
+                log::scalar(es.generation, "es_center", center);
+            }
+        }
+
+        // Behaviour cloning from demonstrations: `--pretrain a.bin,b.bin [--epochs N]`.
+        // Writes weights.bin, which --train / --best then pick up.
+        "pretrain" => {
+            let game = Game::from_config(&game_cfg);
+            let Some(list) = flag!("--pretrain", String) else {
+                eprintln!("fatal: --pretrain needs a comma-separated list of demo files");
+                return;
+            };
+            let demos = load_demo_files(&list, encoder.dim(&game));
+            if demos.is_empty() {
+                eprintln!("fatal: no demonstration steps loaded");
+                return;
+            }
+            // Behaviour cloning starts from fresh weights and writes a complete new checkpoint
+            // (weights, optimizer and agent state), replacing any previous one.
+            let mut agent = DQNAgent::fresh(overrides.apply(single_config(encoder.shape(&game), &arch), &game_cfg));
+            if std::path::Path::new("weights.bin").exists() {
+                log::warn("pretraining from fresh weights; the existing weights.bin will be overwritten");
+            }
+            let epochs: usize = flag!("--epochs").unwrap_or(20);
+            let batch_size = 128;
+            let mut rng = LcgRng::new(99);
+            let mut order: Vec<usize> = (0..demos.len()).collect();
+
+            for epoch in 0..epochs {
+                // Fisher–Yates shuffle per epoch.
+                for i in (1..order.len()).rev() {
+                    let j = rng.gen_range_u32(i as u32 + 1) as usize;
+                    order.swap(i, j);
+                }
+                let (mut loss_sum, mut acc_sum, mut batches) = (0.0f32, 0.0f32, 0usize);
+                for chunk in order.chunks(batch_size) {
+                    let batch: Vec<&demo::DemoStep> = chunk.iter().map(|&i| &demos[i]).collect();
+                    let (loss, acc) = agent.fit_bc(&batch);
+                    if agent.halted() {
+                        return;
+                    }
+                    loss_sum += loss;
+                    acc_sum += acc;
+                    batches += 1;
+                }
+                let n = batches.max(1) as f32;
+                log::info(&format!(
+                    "BC epoch {:3} | loss {:.4} | acc {:.3} | samples {}",
+                    epoch, loss_sum / n, acc_sum / n, demos.len(),
+                ));
+                log::scalar(epoch as u64, "bc_loss", loss_sum / n);
+                log::scalar(epoch as u64, "bc_acc", acc_sum / n);
+            }
+            agent.save_all();
+        }
+
         _ => {}
     }
 }
+
+/// DQN config used for headless training.
+fn train_config(obs_dim: usize) -> AgentConfig {
+    AgentConfig {
+        obs_dim,
+        act_dim: 3,
+        buffer_capacity: 100_000,
+        batch_size: 128,
+        gamma: 0.99,
+        lr: Schedule::Constant(2.5e-4), // ↓ safer LR
+        eps: Schedule::Linear { start: 1.0, end: 0.05, steps: 100_000 },
+        target_update: TargetUpdate::Soft { tau: 0.005 },
+        double_dqn: true,
+        learn_start: 5_000,
+        updates_per_step: 1,   // ↓ fewer updates per step for stability
+        seed: 1234567,
+        demo_margin: 0.8,      // DQfD defaults
+        demo_lambda: 1.0,
+        weights_path: "weights.bin".to_string(),
+        state_path: "agent_state.bin".to_string(),
+        optim_path: "optim_state.bin".to_string(),
+        obs_shape: (obs_dim, 1, 1),
+        arch: LayerSpec::mlp(64, 64),
+        seq_len: 16,
+        burn_in: 8,
+        frame_stack: 1,
+        explore: Exploration::default(),
+        optim: OptimSpec::default(),
+        rails: Rails::default(),
+    }
+}
+
+/// Headless DQN config for a single-snake agent: observation shape from the encoder,
+/// network layers from `--net`.
+fn single_config(shape: ObsShape, arch: &[LayerSpec]) -> AgentConfig {
+    let mut cfg = train_config(shape.size());
+    cfg.obs_shape = shape.chw();
+    cfg.arch = arch.to_vec();
+    cfg
+}
+
+/// Headless DQN config for arena snakes (flat per-snake features), with its own checkpoint files.
+fn arena_config(obs_dim: usize, arch: &[LayerSpec]) -> Result<AgentConfig, String> {
+    Net::check_spec((obs_dim, 1, 1), arch)?;
+    if LayerSpec::is_recurrent(arch) {
+        return Err("with a gru layer is not supported in the arena".to_string());
+    }
+    let mut cfg = train_config(obs_dim);
+    cfg.arch = arch.to_vec();
+    cfg.weights_path = "arena_weights.bin".to_string();
+    cfg.state_path = "arena_state.bin".to_string();
+    cfg.optim_path = "arena_optim.bin".to_string();
+    Ok(cfg)
+}
+
+/// DQN settings given on the command line (`--lr`, `--eps`, `--explore`, `--optim`, the
+/// safety rails and the target options) that replace the mode's defaults.
+struct AgentOverrides {
+    lr: Option<Schedule>,
+    eps: Option<Schedule>,
+    explore: Option<Exploration>,
+    optim: Option<OptimSpec>,
+    grad_clip: Option<f32>,
+    param_clip: Option<f32>,
+    reward_clip: Option<f32>,
+    target_clip: Option<f32>,
+    nan_halt: Option<u64>,
+    nan_dump: Option<String>,
+    target_update: Option<TargetUpdate>,
+    vanilla: bool,
+}
+
+impl AgentOverrides {
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let schedule = |flag: &str| {
+            arg_value::<String>(args, flag)?
+                .map(|spec| Schedule::parse(&spec).map_err(|e| format!("{flag} {e}")))
+                .transpose()
+        };
+        // A clip of 0 disables the rail.
+        let clip = |flag: &str| -> Result<Option<f32>, String> {
+            match arg_value::<f32>(args, flag)? {
+                Some(v) if v < 0.0 || v.is_nan() => Err(format!("{flag} must be non-negative")),
+                Some(v) => Ok(Some(if v == 0.0 { f32::INFINITY } else { v })),
+                None => Ok(None),
+            }
+        };
+        Ok(Self {
+            lr: schedule("--lr")?,
+            eps: schedule("--eps")?,
+            explore: arg_value::<String>(args, "--explore")?.map(|spec| Exploration::parse(&spec)).transpose()?,
+            optim: arg_value::<String>(args, "--optim")?.map(|spec| OptimSpec::parse(&spec)).transpose()?,
+            grad_clip: clip("--grad-clip")?,
+            param_clip: clip("--param-clip")?,
+            reward_clip: clip("--reward-clip")?,
+            target_clip: clip("--target-clip")?,
+            nan_halt: arg_value(args, "--nan-halt")?,
+            nan_dump: arg_value(args, "--nan-dump")?,
+            target_update: arg_value::<String>(args, "--target-update")?.map(|spec| TargetUpdate::parse(&spec)).transpose()?,
+            vanilla: args.contains(&"--vanilla-dqn".to_string()),
+        })
+    }
+
+    fn apply(&self, mut cfg: AgentConfig, game_cfg: &GameConfig) -> AgentConfig {
+        // A bonus is worth BONUS_POINTS apples, above the default reward clip of 1; raise the
+        // clip so it does not train exactly like an apple, unless --reward-clip was given.
+        if game_cfg.typed_food() && self.reward_clip.is_none() {
+            cfg.rails.reward_clip = cfg.rails.reward_clip.max(game::BONUS_POINTS as f32);
+        }
+        if let Some(s) = &self.lr { cfg.lr = s.clone(); }
+        if let Some(s) = &self.eps { cfg.eps = s.clone(); }
+        if let Some(x) = &self.explore { cfg.explore = x.clone(); }
+        if let Some(o) = &self.optim { cfg.optim = o.clone(); }
+        if let Some(v) = self.grad_clip { cfg.rails.max_grad_norm = v; }
+        if let Some(v) = self.param_clip { cfg.rails.param_clip = v; }
+        if let Some(v) = self.reward_clip { cfg.rails.reward_clip = v; }
+        if let Some(v) = self.target_clip { cfg.rails.target_clip = v; }
+        if let Some(n) = self.nan_halt { cfg.rails.nan_halt = n; }
+        if let Some(p) = &self.nan_dump { cfg.rails.dump_prefix = p.clone(); }
+        if let Some(t) = self.target_update { cfg.target_update = t; }
+        if self.vanilla { cfg.double_dqn = false; }
+        cfg
+    }
+}
+
+/// Load and concatenate demonstration files from a comma-separated list (bad files are skipped).
+fn load_demo_files(list: &str, obs_dim: usize) -> Vec<demo::DemoStep> {
+    let mut out = Vec::new();
+    for path in list.split(',').filter(|p| !p.is_empty()) {
+        match demo::load(path, obs_dim) {
+            Ok(mut steps) => {
+                log::info(&format!("loaded {} demonstration steps from {path}", steps.len()));
+                out.append(&mut steps);
+            }
+            Err(e) => log::warn(&format!("cannot load demonstrations {path}: {e}")),
+        }
+    }
+    out
+}
+
+/// Parse the value following a flag, e.g. `--sims 400`. `None` if the flag is absent;
+/// a flag without a value or with a value that does not parse is an error.
+fn arg_value<T: std::str::FromStr>(args: &[String], flag: &str) -> Result<Option<T>, String> {
+    let Some(i) = args.iter().position(|a| a == flag) else { return Ok(None) };
+    let v = args.get(i + 1).ok_or_else(|| format!("{flag} needs a value"))?;
+    v.parse().map(Some).map_err(|_| format!("{flag}: cannot parse '{v}'"))
+}
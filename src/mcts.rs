@@ -0,0 +1,191 @@
+// Monte Carlo Tree Search over cloned game states.
+//
+// Every node owns a snapshot of the game, so expanding a child is just
+// "clone the parent, step_ai(a)". The food RNG is part of the snapshot, which
+// makes the search tree an exact model of the real environment.
+//
+// Leaf evaluation:
+// - without a network: a random rollout of limited depth (discounted return);
+// - with a network (AlphaZero style): priors = softmax(Q / T), value = max Q.
+
+use crate::encoder::ObsEncoder;
+use crate::game::Game;
+use crate::network::Net;
+use crate::utils::*;
+
+/// Number of relative actions (left, straight, right).
+const ACTIONS: usize = 3;
+
+/// Search hyperparameters.
+pub struct MctsConfig {
+    pub simulations: usize,   // simulations per move
+    pub c_puct: f32,          // exploration constant in PUCT
+    pub gamma: f32,           // discount used for backups and rollouts
+    pub rollout_depth: usize, // max random rollout length (no-net mode)
+    pub prior_temp: f32,      // softmax temperature for net priors
+    pub seed: u64,            // RNG seed for rollouts
+    pub encoder: ObsEncoder,  // net input encoding
+}
+
+/// Result of a search from the root: chosen action and per-action statistics.
+pub struct SearchResult {
+    pub action: u8,        // most visited root action
+    pub q: [f32; ACTIONS], // root action-value estimates (Q target; 0 where unvisited)
+    pub visits: [u32; ACTIONS], // root visit counts
+}
+
+// One search node: state after the edge that leads here, plus edge statistics to children.
+struct Node {
+    game: Game,
+    done: bool,
+    prior: [f32; ACTIONS],
+    children: [Option<usize>; ACTIONS],
+    edge_reward: [f32; ACTIONS], // immediate reward of each expanded edge
+    visits: [u32; ACTIONS],
+    value_sum: [f32; ACTIONS],
+}
+
+pub struct MctsAgent {
+    cfg: MctsConfig,
+    net: Option<Net>,
+    rng: LcgRng,
+    nodes: Vec<Node>, // arena, cleared at every search
+    scratch: Option<Game>, // reusable state for rollouts
+}
+
+impl MctsAgent {
+    /// Create an agent; pass `Some(net)` to use it as a prior/value estimator.
+    pub fn new(cfg: MctsConfig, net: Option<Net>) -> Self {
+        let rng = LcgRng::new(cfg.seed);
+        Self { cfg, net, rng, nodes: Vec::new(), scratch: None }
+    }
+
+    /// Mutable access to the evaluator network (e.g. to sync freshly trained weights).
+    pub fn net_mut(&mut self) -> Option<&mut Net> { self.net.as_mut() }
+
+    /// Run the search from `game` and return the most visited root action.
+    pub fn search(&mut self, game: &Game) -> SearchResult {
+        self.nodes.clear();
+        let root_prior = self.priors(game);
+        self.nodes.push(Node::new(game.snapshot(), false, root_prior));
+
+        let mut path: Vec<(usize, usize)> = Vec::new(); // (node, action) along the descent
+        for _ in 0..self.cfg.simulations {
+            path.clear();
+            let mut cur = 0usize;
+
+            // Selection / expansion.
+            let leaf_value = loop {
+                let a = self.select(cur);
+                path.push((cur, a));
+                match self.nodes[cur].children[a] {
+                    Some(child) if !self.nodes[child].done => { cur = child; }
+                    Some(_) => break 0.0, // terminal child: no future value
+                    None => {
+                        let mut g = self.nodes[cur].game.snapshot();
+                        let out = g.step_ai(a as u8);
+                        let value = if out.done { 0.0 } else { self.evaluate(&g) };
+                        let prior = if out.done { [0.0; ACTIONS] } else { self.priors(&g) };
+                        let id = self.nodes.len();
+                        self.nodes.push(Node::new(g, out.done, prior));
+                        self.nodes[cur].children[a] = Some(id);
+                        self.nodes[cur].edge_reward[a] = out.reward;
+                        break value;
+                    }
+                }
+            };
+
+            // Backup: G ← r + γ G from the leaf towards the root.
+            let mut g_ret = leaf_value;
+            for &(n, a) in path.iter().rev() {
+                g_ret = self.nodes[n].edge_reward[a] + self.cfg.gamma * g_ret;
+                self.nodes[n].visits[a] += 1;
+                self.nodes[n].value_sum[a] += g_ret;
+            }
+        }
+
+        let root = &self.nodes[0];
+        let q: [f32; ACTIONS] = std::array::from_fn(|a| {
+            if root.visits[a] > 0 { root.value_sum[a] / root.visits[a] as f32 } else { 0.0 }
+        });
+        // Most visited action; ties broken by value.
+        let mut best = 0usize;
+        for a in 1..ACTIONS {
+            if root.visits[a] > root.visits[best] || (root.visits[a] == root.visits[best] && q[a] > q[best]) {
+                best = a;
+            }
+        }
+        SearchResult { action: best as u8, q, visits: root.visits }
+    }
+
+    // PUCT selection at node `n`.
+    fn select(&self, n: usize) -> usize {
+        let node = &self.nodes[n];
+        let total: u32 = node.visits.iter().sum();
+        let sqrt_n = ((total + 1) as f32).sqrt();
+        let mut best_a = 0usize;
+        let mut best_s = f32::NEG_INFINITY;
+        for a in 0..ACTIONS {
+            let q = if node.visits[a] > 0 { node.value_sum[a] / node.visits[a] as f32 } else { 0.0 };
+            let u = self.cfg.c_puct * node.prior[a] * sqrt_n / (1.0 + node.visits[a] as f32);
+            if q + u > best_s {
+                best_s = q + u;
+                best_a = a;
+            }
+        }
+        best_a
+    }
+
+    // Action priors at a state: softmax over net Q-values, or uniform.
+    fn priors(&mut self, game: &Game) -> [f32; ACTIONS] {
+        let temp = self.cfg.prior_temp.max(1e-3);
+        let Some(net) = self.net.as_mut() else { return [1.0 / ACTIONS as f32; ACTIONS]; };
+        let q = net.forward(&self.cfg.encoder.encode(game));
+        if has_non_finite(&q) { return [1.0 / ACTIONS as f32; ACTIONS]; }
+        let m = q.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let mut p = [0.0f32; ACTIONS];
+        let mut sum = 0.0f32;
+        for a in 0..ACTIONS {
+            p[a] = ((q[a] - m) / temp).exp();
+            sum += p[a];
+        }
+        for v in &mut p { *v /= sum; }
+        p
+    }
+
+    // Value of a non-terminal leaf: max Q from the net, or a random rollout.
+    fn evaluate(&mut self, game: &Game) -> f32 {
+        if let Some(net) = self.net.as_mut() {
+            let q = net.forward(&self.cfg.encoder.encode(game));
+            if !has_non_finite(&q) { return q[argmax(&q)]; }
+        }
+        let g = match self.scratch.as_mut() {
+            Some(g) => { g.restore(game); g }
+            None => self.scratch.insert(game.snapshot()),
+        };
+        let mut ret = 0.0f32;
+        let mut disc = 1.0f32;
+        for _ in 0..self.cfg.rollout_depth {
+            let a = self.rng.gen_range_u32(ACTIONS as u32) as u8;
+            let out = g.step_ai(a);
+            ret += disc * out.reward;
+            disc *= self.cfg.gamma;
+            if out.done { break; }
+        }
+        ret
+    }
+}
+
+impl Node {
+    fn new(game: Game, done: bool, prior: [f32; ACTIONS]) -> Self {
+        Self {
+            game,
+            done,
+            prior,
+            children: [None; ACTIONS],
+            edge_reward: [0.0; ACTIONS],
+            visits: [0; ACTIONS],
+            value_sum: [0.0; ACTIONS],
+        }
+    }
+}
@@ -1,12 +1,16 @@
-/// network, backpropagation, AdamW, save/load
+//! network, backpropagation, save/load (optimizers live in `optim`)
+//! `Net` is a sequence of layers (Linear, Conv2d, GRU, LayerNorm, Dropout, activations,
+//! flatten) built from a spec; nets with a GRU also train on sequences (BPTT).
 
 use std::fs::File;
 use std::io::{Read, Write};
+use crate::optim::{Optimizer, Param};
 use crate::utils::*;
 
 /// Linear layer: Y = X * W + b
 /// We store weights in row-major as matrix (in_dim x out_dim):
 /// W[i*out+j] = weight from input i to output j
+#[derive(Clone)]
 pub struct Linear {
     pub in_dim: usize,
     pub out_dim: usize,
@@ -15,22 +19,14 @@ pub struct Linear {
     // accumulated gradients:
     pub gw: Vec<f32>,  // dL/dW
     pub gb: Vec<f32>,  // dL/dB
-    // Adam moments:
-    mw: Vec<f32>, vw: Vec<f32>,
-    mb: Vec<f32>, vb: Vec<f32>,
     // cache:
     last_x: Vec<f32>,
 }
 
 impl Linear {
-    /// Create a layer and initialize weights with Xavier/Glorot uniform.
-    pub fn new(in_dim: usize, out_dim: usize, rng: &mut LcgRng) -> Self {
-        let k = (6.0f32 / (in_dim as f32 + out_dim as f32)).sqrt();
-        let mut w = vec![0.0; in_dim * out_dim];
-        for v in &mut w {
-            let u = rng.next_f32();          // u ∈ [0,1)
-            *v = -k + 2.0 * k * u;           // scale to [−k, k]
-        }
+    /// Create a layer and initialize weights with `init` (biases start at zero).
+    pub fn new(in_dim: usize, out_dim: usize, init: Init, rng: &mut LcgRng) -> Self {
+        let w = init.weights(in_dim, out_dim, in_dim, out_dim, rng);
         Self {
             in_dim,
             out_dim,
@@ -38,10 +34,6 @@ impl Linear {
             b: vec![0.0; out_dim],
             gw: vec![0.0; in_dim * out_dim],
             gb: vec![0.0; out_dim],
-            mw: vec![0.0; in_dim * out_dim],
-            vw: vec![0.0; in_dim * out_dim],
-            mb: vec![0.0; out_dim],
-            vb: vec![0.0; out_dim],
             last_x: vec![0.0; in_dim],
         }
     }
@@ -56,13 +48,11 @@ impl Linear {
         debug_assert_eq!(x.len(), self.in_dim);
         self.last_x.copy_from_slice(x);
 
-        let mut y = vec![0.0f32; self.out_dim];
-        for j in 0..self.out_dim {
-            let mut acc = self.b[j];
-            for i in 0..self.in_dim {
-                acc += x[i] * self.w[i * self.out_dim + j];
+        let mut y = self.b.clone();
+        for (&xi, row) in x.iter().zip(self.w.chunks(self.out_dim)) {
+            for (yj, &w) in y.iter_mut().zip(row) {
+                *yj += xi * w;
             }
-            y[j] = acc;
         }
         y
     }
@@ -72,172 +62,1110 @@ impl Linear {
         debug_assert_eq!(dy.len(), self.out_dim);
 
         // dW = X^T * dY
-        for i in 0..self.in_dim {
-            let xi = self.last_x[i];
-            let row = i * self.out_dim;
-            for j in 0..self.out_dim {
-                self.gw[row + j] += xi * dy[j];
+        for (&xi, row) in self.last_x.iter().zip(self.gw.chunks_mut(self.out_dim)) {
+            for (g, &d) in row.iter_mut().zip(dy) {
+                *g += xi * d;
             }
         }
         // dB = dY
-        for j in 0..self.out_dim {
-            self.gb[j] += dy[j];
+        for (g, &d) in self.gb.iter_mut().zip(dy) {
+            *g += d;
         }
 
         // dX = dY * W^T
-        let mut dx = vec![0.0f32; self.in_dim];
-        for i in 0..self.in_dim {
-            let mut acc = 0.0f32;
-            let row = i * self.out_dim;
-            for j in 0..self.out_dim {
-                acc += dy[j] * self.w[row + j];
+        self.w
+            .chunks(self.out_dim)
+            .map(|row| row.iter().zip(dy).fold(0.0f32, |acc, (&w, &d)| acc + d * w))
+            .collect()
+    }
+
+    /// L2 sum of gradients (for global clip).
+    pub fn grad_l2_sum(&self) -> f32 {
+        let mut s = 0.0;
+        for g in &self.gw { s += g * g; }
+        for g in &self.gb { s += g * g; }
+        s
+    }
+
+    pub fn non_finite_in_params_or_grads(&self) -> bool {
+        has_non_finite(&self.w) || has_non_finite(&self.b) ||
+            has_non_finite(&self.gw) || has_non_finite(&self.gb)
+    }
+}
+
+/// Weight initializer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Init {
+    Xavier,     // Glorot uniform, U(±√(6 / (fan_in + fan_out)))
+    He,         // Kaiming normal, N(0, 2 / fan_in) — for ReLU-like activations
+    Orthogonal, // random (semi-)orthogonal matrix
+    Zero,       // all zeros; only for the output layer, so initial Q-values are 0
+}
+
+impl Init {
+    pub fn name(self) -> &'static str {
+        match self {
+            Init::Xavier => "xavier",
+            Init::He => "he",
+            Init::Orthogonal => "orth",
+            Init::Zero => "zero",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Init> {
+        match s {
+            "xavier" => Some(Init::Xavier),
+            "he" => Some(Init::He),
+            "orth" => Some(Init::Orthogonal),
+            "zero" => Some(Init::Zero),
+            _ => None,
+        }
+    }
+
+    /// Weights of a `rows`×`cols` matrix in storage order. Orthogonality does not depend
+    /// on which side is the input, so Linear ([in][out]) and Conv2d ([out][in·k·k]) share it.
+    fn weights(self, fan_in: usize, fan_out: usize, rows: usize, cols: usize, rng: &mut LcgRng) -> Vec<f32> {
+        let n = rows * cols;
+        match self {
+            Init::Xavier => {
+                let k = (6.0f32 / (fan_in as f32 + fan_out as f32)).sqrt();
+                (0..n).map(|_| -k + 2.0 * k * rng.next_f32()).collect() // u ∈ [0,1) → [−k, k]
+            }
+            Init::He => {
+                let std = (2.0f32 / fan_in.max(1) as f32).sqrt();
+                (0..n).map(|_| std * rng.next_gaussian()).collect()
             }
-            dx[i] = acc;
+            Init::Orthogonal => {
+                let mut m: Vec<f32> = (0..n).map(|_| rng.next_gaussian()).collect();
+                // Gram–Schmidt over the shorter side: orthonormal rows if rows ≤ cols, else columns.
+                let (vecs, len) = if rows <= cols { (rows, cols) } else { (cols, rows) };
+                let idx = |v: usize, i: usize| if rows <= cols { v * cols + i } else { i * cols + v };
+                for v in 0..vecs {
+                    for u in 0..v {
+                        let dot: f32 = (0..len).map(|i| m[idx(v, i)] * m[idx(u, i)]).sum();
+                        for i in 0..len { m[idx(v, i)] -= dot * m[idx(u, i)]; }
+                    }
+                    let norm = (0..len).map(|i| m[idx(v, i)] * m[idx(v, i)]).sum::<f32>().sqrt().max(1e-8);
+                    for i in 0..len { m[idx(v, i)] /= norm; }
+                }
+                m
+            }
+            Init::Zero => vec![0.0; n],
         }
-        dx
+    }
+}
+
+/// Conv block hyperparameters: `out_ch` filters of `kernel`×`kernel`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConvSpec {
+    pub out_ch: usize,
+    pub kernel: usize,
+    pub stride: usize,
+    pub padding: usize,
+}
+
+/// 2-D convolution over a (channels, height, width) input stored channel-major.
+/// Weights are [out_ch][in_ch][k][k]; output is (out_ch, out_h, out_w), also channel-major.
+#[derive(Clone)]
+pub struct Conv2d {
+    pub in_ch: usize,
+    pub in_h: usize,
+    pub in_w: usize,
+    pub spec: ConvSpec,
+    pub out_h: usize,
+    pub out_w: usize,
+    pub w: Vec<f32>,
+    pub b: Vec<f32>,
+    pub gw: Vec<f32>,
+    pub gb: Vec<f32>,
+    last_x: Vec<f32>,
+}
+
+impl Conv2d {
+    /// Create a layer for an `in_ch`×`in_h`×`in_w` input, weights from `init` like `Linear`.
+    pub fn new(in_ch: usize, in_h: usize, in_w: usize, spec: ConvSpec, init: Init, rng: &mut LcgRng) -> Self {
+        let k = spec.kernel;
+        let out_h = (in_h + 2 * spec.padding).saturating_sub(k) / spec.stride + 1;
+        let out_w = (in_w + 2 * spec.padding).saturating_sub(k) / spec.stride + 1;
+        let n = spec.out_ch * in_ch * k * k;
+        let w = init.weights(in_ch * k * k, spec.out_ch * k * k, spec.out_ch, in_ch * k * k, rng);
+        Self {
+            in_ch, in_h, in_w, spec, out_h, out_w,
+            w,
+            b: vec![0.0; spec.out_ch],
+            gw: vec![0.0; n],
+            gb: vec![0.0; spec.out_ch],
+            last_x: vec![0.0; in_ch * in_h * in_w],
+        }
+    }
+
+    pub fn out_len(&self) -> usize { self.spec.out_ch * self.out_h * self.out_w }
+
+    // Input cell feeding output (oy, ox) at kernel offset (ky, kx); None in the padding.
+    #[inline]
+    fn src(&self, oy: usize, ox: usize, ky: usize, kx: usize) -> Option<usize> {
+        let iy = (oy * self.spec.stride + ky) as isize - self.spec.padding as isize;
+        let ix = (ox * self.spec.stride + kx) as isize - self.spec.padding as isize;
+        if iy < 0 || ix < 0 || iy >= self.in_h as isize || ix >= self.in_w as isize {
+            return None;
+        }
+        Some(iy as usize * self.in_w + ix as usize)
     }
 
-    /// AdamW step for this layer (with global grad scaling and decoupled weight decay).
-    pub fn step_adam(
-        &mut self,
-        lr: f32,
-        b1: f32,
-        b2: f32,
-        eps: f32,
-        t: u64,
-        grad_scale: f32,
-        weight_decay: f32,   // decoupled L2 on weights (not on bias)
-    ) {
-        let t_f = t as f32;
-        let b1t = b1.powf(t_f);
-        let b2t = b2.powf(t_f);
-        let corr1 = 1.0 - b1t;
-        let corr2 = 1.0 - b2t;
-
-        // Weights
-        for i in 0..self.w.len() {
-            let g = self.gw[i] * grad_scale;
-            self.mw[i] = b1 * self.mw[i] + (1.0 - b1) * g;
-            self.vw[i] = b2 * self.vw[i] + (1.0 - b2) * (g * g);
-            let m_hat = self.mw[i] / corr1.max(1e-8);
-            let v_hat = self.vw[i] / corr2.max(1e-8);
-            // Adam update
-            self.w[i] -= lr * m_hat / (v_hat.sqrt() + eps);
-            // Decoupled weight decay (AdamW)
-            if weight_decay > 0.0 {
-                self.w[i] -= lr * weight_decay * self.w[i];
+    pub fn zero_grad(&mut self) {
+        for g in &mut self.gw { *g = 0.0; }
+        for g in &mut self.gb { *g = 0.0; }
+    }
+
+    /// Forward pass for one sample.
+    pub fn forward(&mut self, x: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(x.len(), self.last_x.len());
+        self.last_x.copy_from_slice(x);
+        let (k, plane) = (self.spec.kernel, self.in_h * self.in_w);
+        let mut y = vec![0.0f32; self.out_len()];
+        for o in 0..self.spec.out_ch {
+            for oy in 0..self.out_h {
+                for ox in 0..self.out_w {
+                    let mut acc = self.b[o];
+                    for ky in 0..k {
+                        for kx in 0..k {
+                            let Some(s) = self.src(oy, ox, ky, kx) else { continue };
+                            for c in 0..self.in_ch {
+                                acc += self.w[((o * self.in_ch + c) * k + ky) * k + kx] * x[c * plane + s];
+                            }
+                        }
+                    }
+                    y[(o * self.out_h + oy) * self.out_w + ox] = acc;
+                }
             }
         }
-        // Bias (no decay)
-        for i in 0..self.b.len() {
-            let g = self.gb[i] * grad_scale;
-            self.mb[i] = b1 * self.mb[i] + (1.0 - b1) * g;
-            self.vb[i] = b2 * self.vb[i] + (1.0 - b2) * (g * g);
-            let m_hat = self.mb[i] / corr1.max(1e-8);
-            let v_hat = self.vb[i] / corr2.max(1e-8);
-            self.b[i] -= lr * m_hat / (v_hat.sqrt() + eps);
+        y
+    }
+
+    /// Backward pass: accumulate dW, dB and return dX.
+    pub fn backward(&mut self, dy: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(dy.len(), self.out_len());
+        let (k, plane) = (self.spec.kernel, self.in_h * self.in_w);
+        let mut dx = vec![0.0f32; self.last_x.len()];
+        for o in 0..self.spec.out_ch {
+            for oy in 0..self.out_h {
+                for ox in 0..self.out_w {
+                    let d = dy[(o * self.out_h + oy) * self.out_w + ox];
+                    if d == 0.0 { continue; }
+                    self.gb[o] += d;
+                    for ky in 0..k {
+                        for kx in 0..k {
+                            let Some(s) = self.src(oy, ox, ky, kx) else { continue };
+                            for c in 0..self.in_ch {
+                                let wi = ((o * self.in_ch + c) * k + ky) * k + kx;
+                                self.gw[wi] += d * self.last_x[c * plane + s];
+                                dx[c * plane + s] += d * self.w[wi];
+                            }
+                        }
+                    }
+                }
+            }
         }
+        dx
     }
 
-    /// L2 sum of gradients (for global clip).
     pub fn grad_l2_sum(&self) -> f32 {
-        let mut s = 0.0;
-        for g in &self.gw { s += g * g; }
-        for g in &self.gb { s += g * g; }
-        s
+        self.gw.iter().chain(&self.gb).map(|g| g * g).sum()
     }
 
     pub fn non_finite_in_params_or_grads(&self) -> bool {
         has_non_finite(&self.w) || has_non_finite(&self.b) ||
             has_non_finite(&self.gw) || has_non_finite(&self.gb)
     }
+}
+
+/// Flatten (c, h, w) feature maps into a plain vector. Our feature maps are already
+/// stored flat and channel-major, so this only checks and reports sizes.
+#[derive(Clone)]
+pub struct Flatten {
+    pub len: usize,
+}
+
+impl Flatten {
+    pub fn forward(&self, x: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(x.len(), self.len);
+        x.to_vec()
+    }
+    pub fn backward(&self, dy: Vec<f32>) -> Vec<f32> {
+        debug_assert_eq!(dy.len(), self.len);
+        dy
+    }
+}
+
+/// Elementwise activation function.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Activation {
+    Relu,
+    LeakyRelu, // slope LEAKY_SLOPE for x < 0
+    Elu,       // α = 1
+    Tanh,
+    Gelu,      // tanh approximation
+    Silu,      // x·σ(x), a.k.a. swish
+}
+
+const LEAKY_SLOPE: f32 = 0.01;
+const ALL_ACTIVATIONS: [Activation; 6] =
+    [Activation::Relu, Activation::LeakyRelu, Activation::Elu, Activation::Tanh, Activation::Gelu, Activation::Silu];
+
+impl Activation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Activation::Relu => "relu",
+            Activation::LeakyRelu => "leaky",
+            Activation::Elu => "elu",
+            Activation::Tanh => "tanh",
+            Activation::Gelu => "gelu",
+            Activation::Silu => "silu",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Activation> {
+        ALL_ACTIVATIONS.into_iter().find(|a| a.name() == s)
+    }
+
+    // Tag in the weights file (index in ALL_ACTIVATIONS; never reorder).
+    fn code(self) -> u32 {
+        ALL_ACTIVATIONS.iter().position(|&a| a == self).unwrap() as u32
+    }
+
+    fn from_code(c: u32) -> Option<Activation> {
+        ALL_ACTIVATIONS.get(c as usize).copied()
+    }
+
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Relu => x.max(0.0),
+            Activation::LeakyRelu => if x > 0.0 { x } else { LEAKY_SLOPE * x },
+            Activation::Elu => if x > 0.0 { x } else { x.exp() - 1.0 },
+            Activation::Tanh => x.tanh(),
+            Activation::Gelu => 0.5 * x * (1.0 + gelu_inner(x).tanh()),
+            Activation::Silu => x * sigmoid(x),
+        }
+    }
+
+    /// d apply / dx at the pre-activation `x`.
+    fn derivative(self, x: f32) -> f32 {
+        match self {
+            Activation::Relu => if x > 0.0 { 1.0 } else { 0.0 },
+            Activation::LeakyRelu => if x > 0.0 { 1.0 } else { LEAKY_SLOPE },
+            Activation::Elu => if x > 0.0 { 1.0 } else { x.exp() },
+            Activation::Tanh => 1.0 - x.tanh().powi(2),
+            Activation::Gelu => {
+                let t = gelu_inner(x).tanh();
+                0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * GELU_C * (1.0 + 3.0 * 0.044715 * x * x)
+            }
+            Activation::Silu => {
+                let s = sigmoid(x);
+                s * (1.0 + x * (1.0 - s))
+            }
+        }
+    }
+}
+
+const GELU_C: f32 = 0.797_884_6; // √(2/π)
+
+fn gelu_inner(x: f32) -> f32 { GELU_C * (x + 0.044715 * x * x * x) }
+
+fn sigmoid(x: f32) -> f32 { 1.0 / (1.0 + (-x).exp()) }
+
+/// Activation layer; caches its input for the backward pass.
+#[derive(Clone)]
+struct Act {
+    kind: Activation,
+    last_x: Vec<f32>,
+}
+
+impl Act {
+    fn forward(&mut self, mut z: Vec<f32>) -> Vec<f32> {
+        self.last_x.clone_from(&z);
+        for v in &mut z { *v = self.kind.apply(*v); }
+        z
+    }
+
+    fn backward(&self, mut da: Vec<f32>) -> Vec<f32> {
+        debug_assert_eq!(da.len(), self.last_x.len());
+        for (d, &x) in da.iter_mut().zip(&self.last_x) {
+            *d *= self.kind.derivative(x);
+        }
+        da
+    }
+}
+
+/// Layer normalization over the whole feature vector of one sample, with learnable
+/// per-feature gain and bias: y = g · (x − μ) / √(σ² + ε) + b.
+#[derive(Clone)]
+pub struct LayerNorm {
+    pub dim: usize,
+    pub g: Vec<f32>,   // gain (starts at 1)
+    pub b: Vec<f32>,   // bias (starts at 0)
+    pub gg: Vec<f32>,  // dL/dg
+    pub gb: Vec<f32>,  // dL/db
+    // cache:
+    last_xhat: Vec<f32>,
+    last_inv_std: f32,
+}
+
+const LN_EPS: f32 = 1e-5;
+
+impl LayerNorm {
+    pub fn new(dim: usize) -> Self {
+        Self {
+            dim,
+            g: vec![1.0; dim],
+            b: vec![0.0; dim],
+            gg: vec![0.0; dim],
+            gb: vec![0.0; dim],
+            last_xhat: vec![0.0; dim],
+            last_inv_std: 0.0,
+        }
+    }
+
+    pub fn zero_grad(&mut self) {
+        for g in self.gg.iter_mut().chain(self.gb.iter_mut()) { *g = 0.0; }
+    }
+
+    /// Forward pass for one sample.
+    pub fn forward(&mut self, x: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(x.len(), self.dim);
+        let n = self.dim as f32;
+        let mean = x.iter().sum::<f32>() / n;
+        let var = x.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / n;
+        self.last_inv_std = 1.0 / (var + LN_EPS).sqrt();
+        let mut y = vec![0.0f32; self.dim];
+        for i in 0..self.dim {
+            self.last_xhat[i] = (x[i] - mean) * self.last_inv_std;
+            y[i] = self.g[i] * self.last_xhat[i] + self.b[i];
+        }
+        y
+    }
+
+    /// Backward pass: accumulate dg, db and return dX.
+    pub fn backward(&mut self, dy: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(dy.len(), self.dim);
+        let n = self.dim as f32;
+        let (mut sum_d, mut sum_dx) = (0.0f32, 0.0f32); // Σ dx̂, Σ dx̂·x̂
+        let mut dxhat = vec![0.0f32; self.dim];
+        for i in 0..self.dim {
+            self.gg[i] += dy[i] * self.last_xhat[i];
+            self.gb[i] += dy[i];
+            dxhat[i] = dy[i] * self.g[i];
+            sum_d += dxhat[i];
+            sum_dx += dxhat[i] * self.last_xhat[i];
+        }
+        // dX = inv_std / N · (N·dx̂ − Σdx̂ − x̂·Σ(dx̂·x̂))
+        (0..self.dim)
+            .map(|i| self.last_inv_std / n * (n * dxhat[i] - sum_d - self.last_xhat[i] * sum_dx))
+            .collect()
+    }
+
+    pub fn grad_l2_sum(&self) -> f32 {
+        self.gg.iter().chain(&self.gb).map(|g| g * g).sum()
+    }
+
+    pub fn non_finite_in_params_or_grads(&self) -> bool {
+        has_non_finite(&self.g) || has_non_finite(&self.b) ||
+            has_non_finite(&self.gg) || has_non_finite(&self.gb)
+    }
+}
+
+/// Inverted dropout: in training each unit is zeroed with probability `rate` and the rest
+/// are scaled by 1 / (1 − rate); at inference it is the identity.
+#[derive(Clone)]
+pub struct Dropout {
+    pub rate: f32,
+    mask: Vec<f32>, // 0 or 1 / (1 − rate) per unit of the last training pass
+    rng: LcgRng,
+}
+
+impl Dropout {
+    pub fn new(rate: f32, rng: LcgRng) -> Self { Self { rate, mask: Vec::new(), rng } }
+
+    pub fn forward(&mut self, mut x: Vec<f32>, train: bool) -> Vec<f32> {
+        if !train || self.rate <= 0.0 {
+            self.mask.clear(); // backward passes the gradient through unchanged
+            return x;
+        }
+        let keep = 1.0 - self.rate;
+        self.mask = (0..x.len()).map(|_| if self.rng.next_f32() < keep { 1.0 / keep } else { 0.0 }).collect();
+        for (v, m) in x.iter_mut().zip(&self.mask) { *v *= m; }
+        x
+    }
+
+    pub fn backward(&self, mut dy: Vec<f32>) -> Vec<f32> {
+        if !self.mask.is_empty() {
+            for (d, m) in dy.iter_mut().zip(&self.mask) { *d *= m; }
+        }
+        dy
+    }
+}
+
+/// GRU layer (gate order r, z, n; same equations as PyTorch):
+///   r = σ(Wxr·x + bxr + Whr·h + bhr)
+///   z = σ(Wxz·x + bxz + Whz·h + bhz)
+///   n = tanh(Wxn·x + bxn + r ⊙ (Whn·h + bhn))
+///   h' = (1 − z) ⊙ n + z ⊙ h
+/// The hidden state lives in the layer and advances with every forward pass; the output
+/// is h'. Backward takes the gradient w.r.t. h' and carries dL/dh to the previous step,
+/// so calling it for steps T..1 in order is backprop-through-time.
+#[derive(Clone)]
+pub struct Gru {
+    pub in_dim: usize,
+    pub hidden: usize,
+    pub wx: Vec<f32>,  // [in][3H]
+    pub wh: Vec<f32>,  // [H][3H]
+    pub bx: Vec<f32>,  // 3H
+    pub bh: Vec<f32>,  // 3H
+    pub gwx: Vec<f32>,
+    pub gwh: Vec<f32>,
+    pub gbx: Vec<f32>,
+    pub gbh: Vec<f32>,
+    h: Vec<f32>,      // hidden state
+    // cache of the last step: x, h_prev, r, z, n, Whn·h + bhn
+    last_x: Vec<f32>,
+    last_h: Vec<f32>,
+    last_r: Vec<f32>,
+    last_z: Vec<f32>,
+    last_n: Vec<f32>,
+    last_ghn: Vec<f32>,
+    carry: Vec<f32>,  // dL/dh flowing back from the next step
+}
+
+impl Gru {
+    pub fn new(in_dim: usize, hidden: usize, init: Init, rng: &mut LcgRng) -> Self {
+        let g = 3 * hidden;
+        let wx = init.weights(in_dim, g, in_dim, g, rng);
+        let wh = init.weights(hidden, g, hidden, g, rng);
+        Self {
+            in_dim, hidden, wx, wh,
+            bx: vec![0.0; g], bh: vec![0.0; g],
+            gwx: vec![0.0; in_dim * g], gwh: vec![0.0; hidden * g],
+            gbx: vec![0.0; g], gbh: vec![0.0; g],
+            h: vec![0.0; hidden],
+            last_x: vec![0.0; in_dim],
+            last_h: vec![0.0; hidden],
+            last_r: vec![0.0; hidden],
+            last_z: vec![0.0; hidden],
+            last_n: vec![0.0; hidden],
+            last_ghn: vec![0.0; hidden],
+            carry: vec![0.0; hidden],
+        }
+    }
+
+    pub fn reset_state(&mut self) {
+        for v in &mut self.h { *v = 0.0; }
+    }
+
+    pub fn zero_grad(&mut self) {
+        for g in self.gwx.iter_mut().chain(self.gwh.iter_mut()).chain(self.gbx.iter_mut()).chain(self.gbh.iter_mut()) {
+            *g = 0.0;
+        }
+    }
+
+    /// One time step: consumes x, advances the hidden state and returns it.
+    pub fn forward(&mut self, x: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(x.len(), self.in_dim);
+        let hd = self.hidden;
+        let g = 3 * hd;
+        let mut gx = self.bx.clone();
+        for (&xi, row) in x.iter().zip(self.wx.chunks(g)) {
+            for (a, &w) in gx.iter_mut().zip(row) { *a += xi * w; }
+        }
+        let mut gh = self.bh.clone();
+        for (&hi, row) in self.h.iter().zip(self.wh.chunks(g)) {
+            for (a, &w) in gh.iter_mut().zip(row) { *a += hi * w; }
+        }
+        self.last_x.copy_from_slice(x);
+        self.last_h.clone_from(&self.h);
+        for k in 0..hd {
+            let r = sigmoid(gx[k] + gh[k]);
+            let z = sigmoid(gx[hd + k] + gh[hd + k]);
+            let n = (gx[2 * hd + k] + r * gh[2 * hd + k]).tanh();
+            self.last_r[k] = r;
+            self.last_z[k] = z;
+            self.last_n[k] = n;
+            self.last_ghn[k] = gh[2 * hd + k];
+            self.h[k] = (1.0 - z) * n + z * self.h[k];
+        }
+        self.h.clone()
+    }
 
-    /// Clamp parameters into [-max_abs, max_abs] (hard safety rail).
-    pub fn clamp_params(&mut self, max_abs: f32) {
-        let clamp = |v: &mut f32| {
-            if *v >  max_abs { *v =  max_abs; }
-            if *v < -max_abs { *v = -max_abs; }
+    /// Backward for the cached step: accumulate grads, return dX and keep dL/dh_prev for
+    /// the previous step.
+    pub fn backward(&mut self, dy: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(dy.len(), self.hidden);
+        let hd = self.hidden;
+        let g = 3 * hd;
+        let mut dgx = vec![0.0f32; g]; // d pre-activations (x side), order r z n
+        let mut dgh = vec![0.0f32; g]; // d pre-activations (h side)
+        let mut dh_prev = vec![0.0f32; hd];
+        for k in 0..hd {
+            let (r, z, n) = (self.last_r[k], self.last_z[k], self.last_n[k]);
+            let dh = dy[k] + self.carry[k];
+            let dn = dh * (1.0 - z);
+            let dz = dh * (self.last_h[k] - n);
+            dh_prev[k] = dh * z;
+            let dan = dn * (1.0 - n * n);
+            let dr = dan * self.last_ghn[k];
+            dgx[k] = dr * r * (1.0 - r);
+            dgx[hd + k] = dz * z * (1.0 - z);
+            dgx[2 * hd + k] = dan;
+            dgh[k] = dgx[k];
+            dgh[hd + k] = dgx[hd + k];
+            dgh[2 * hd + k] = dan * r;
+        }
+        for (b, &d) in self.gbx.iter_mut().zip(&dgx) { *b += d; }
+        for (b, &d) in self.gbh.iter_mut().zip(&dgh) { *b += d; }
+        // One row of W per input unit: accumulate dW and return dY · Wᵀ for that unit.
+        let back = |inp: &[f32], w: &[f32], gw: &mut [f32], dg: &[f32]| -> Vec<f32> {
+            inp.iter()
+                .zip(w.chunks(g).zip(gw.chunks_mut(g)))
+                .map(|(&xi, (w_row, g_row))| {
+                    let mut acc = 0.0f32;
+                    for ((gw, &w), &d) in g_row.iter_mut().zip(w_row).zip(dg) {
+                        *gw += xi * d;
+                        acc += d * w;
+                    }
+                    acc
+                })
+                .collect()
         };
-        for w in &mut self.w { clamp(w); }
-        for b in &mut self.b { clamp(b); }
+        let dx = back(&self.last_x, &self.wx, &mut self.gwx, &dgx);
+        let dh_w = back(&self.last_h, &self.wh, &mut self.gwh, &dgh);
+        for (d, a) in dh_prev.iter_mut().zip(dh_w) { *d += a; }
+        self.carry = dh_prev;
+        dx
+    }
+
+    pub fn grad_l2_sum(&self) -> f32 {
+        self.gwx.iter().chain(&self.gwh).chain(&self.gbx).chain(&self.gbh).map(|g| g * g).sum()
+    }
+
+    pub fn non_finite_in_params_or_grads(&self) -> bool {
+        [&self.wx, &self.wh, &self.bx, &self.bh, &self.gwx, &self.gwh, &self.gbx, &self.gbh]
+            .iter().any(|v| has_non_finite(v))
+    }
+
+    // Per-step cache for BPTT: x, h_prev, r, z, n, ghn.
+    fn cache(&self) -> Vec<f32> {
+        [&self.last_x, &self.last_h, &self.last_r, &self.last_z, &self.last_n, &self.last_ghn]
+            .iter().flat_map(|v| v.iter().copied()).collect()
+    }
+
+    fn restore(&mut self, c: &[f32]) {
+        let mut off = 0;
+        for buf in [&mut self.last_x, &mut self.last_h, &mut self.last_r, &mut self.last_z, &mut self.last_n, &mut self.last_ghn] {
+            let n = buf.len();
+            buf.copy_from_slice(&c[off..off + n]);
+            off += n;
+        }
     }
 }
 
-/// ReLU layer with mask.
-struct ReLU {
-    mask: Vec<u8>, // 1 — pass gradient, 0 — mute
+/// One entry of a network spec. The output `Linear` (to the number of actions) is not
+/// part of the spec; `Net` always appends it, with the init of a trailing `Output` entry.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LayerSpec {
+    Linear { out: usize, init: Init },
+    Conv(ConvSpec, Init),
+    Gru { hidden: usize, init: Init },
+    Act(Activation),
+    LayerNorm,
+    Dropout(f32),
+    Flatten,
+    Output(Init),
 }
-impl ReLU {
-    fn new(size: usize) -> Self { Self { mask: vec![0; size] } }
 
-    fn forward(&mut self, z: &mut [f32]) {
-        for i in 0..z.len() {
-            if z[i] > 0.0 {
-                self.mask[i] = 1;
+impl LayerSpec {
+    /// Parse a comma-separated spec: `N` is a linear layer of width N, `conv:out:k:stride:pad`
+    /// a conv block, `gru:H` a GRU with H units, `relu`/`leaky`/`elu`/`tanh`/`gelu`/`silu` an
+    /// activation, `ln` a layer norm, `drop:P` dropout with rate P and `flatten` a flatten.
+    /// Linear, conv and GRU layers take an initializer suffix `@xavier` (default), `@he`
+    /// or `@orth`; a final `out@<init>` (also `@zero`) sets the init of the output layer, e.g.
+    /// `conv:16:3:1:1@he,relu,flatten,128@he,ln,relu,drop:0.1,64,tanh,out@zero`.
+    pub fn parse_list(spec: &str) -> Result<Vec<LayerSpec>, String> {
+        let mut out = Vec::new();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            if matches!(out.last(), Some(LayerSpec::Output(_))) {
+                return Err(format!("'{part}' after the output layer (out@<init> must be last)"));
+            }
+            let (base, init) = match part.split_once('@') {
+                Some((b, i)) => match Init::parse(i) {
+                    Some(init) => (b, init),
+                    None => return Err(format!("unknown init '{i}' in '{part}' (expected xavier, he, orth or zero)")),
+                },
+                None => (part, Init::Xavier),
+            };
+            if init == Init::Zero && base != "out" {
+                return Err(format!("'{part}': zero init is only allowed for the output layer (out@zero)"));
+            }
+            let layer = if let Some(args) = base.strip_prefix("conv:") {
+                let f: Vec<usize> = args.split(':').map(|x| x.parse::<usize>()).collect::<Result<_, _>>()
+                    .map_err(|_| format!("bad conv '{part}' (expected conv:out:kernel:stride:padding)"))?;
+                if f.len() != 4 || f[0] == 0 || f[1] == 0 || f[2] == 0 {
+                    return Err(format!("bad conv '{part}' (expected conv:out:kernel:stride:padding)"));
+                }
+                LayerSpec::Conv(ConvSpec { out_ch: f[0], kernel: f[1], stride: f[2], padding: f[3] }, init)
+            } else if let Some(h) = base.strip_prefix("gru:") {
+                match h.parse::<usize>() {
+                    Ok(hidden) if hidden > 0 => LayerSpec::Gru { hidden, init },
+                    _ => return Err(format!("bad gru '{part}' (expected gru:H)")),
+                }
+            } else if base == "out" {
+                LayerSpec::Output(init)
+            } else if base == "flatten" && base == part {
+                LayerSpec::Flatten
+            } else if base == "ln" && base == part {
+                LayerSpec::LayerNorm
+            } else if let Some(r) = part.strip_prefix("drop:") {
+                match r.parse::<f32>() {
+                    Ok(rate) if (0.0..1.0).contains(&rate) => LayerSpec::Dropout(rate),
+                    _ => return Err(format!("bad dropout '{part}' (expected drop:P with 0 <= P < 1)")),
+                }
+            } else if let Some(a) = Activation::parse(base).filter(|_| base == part) {
+                LayerSpec::Act(a)
             } else {
-                self.mask[i] = 0;
-                z[i] = 0.0;
+                match base.parse::<usize>() {
+                    Ok(n) if n > 0 => LayerSpec::Linear { out: n, init },
+                    _ => return Err(format!(
+                        "unknown layer '{part}' (expected N, conv:o:k:s:p, gru:H, ln, drop:P, flatten, out@<init> or one of {})",
+                        ALL_ACTIVATIONS.map(|a| a.name()).join("/"),
+                    )),
+                }
+            };
+            out.push(layer);
+        }
+        Ok(out)
+    }
+
+    /// The classic head: two hidden layers of `h1` and `h2` units with ReLU.
+    pub fn mlp(h1: usize, h2: usize) -> Vec<LayerSpec> {
+        vec![
+            LayerSpec::Linear { out: h1, init: Init::Xavier }, LayerSpec::Act(Activation::Relu),
+            LayerSpec::Linear { out: h2, init: Init::Xavier }, LayerSpec::Act(Activation::Relu),
+        ]
+    }
+
+    /// True if the spec has a recurrent layer (the net then keeps a hidden state).
+    pub fn is_recurrent(specs: &[LayerSpec]) -> bool {
+        specs.iter().any(|s| matches!(s, LayerSpec::Gru { .. }))
+    }
+
+    /// Inverse of `parse_list`.
+    pub fn list_to_string(specs: &[LayerSpec]) -> String {
+        specs.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(",")
+    }
+
+    // What a weights file has to agree on: initializers and the output entry only matter for
+    // fresh weights, and flatten and dropout are the identity at inference.
+    fn structural(specs: &[LayerSpec]) -> Vec<LayerSpec> {
+        specs.iter().filter_map(|s| match *s {
+            LayerSpec::Linear { out, .. } => Some(LayerSpec::Linear { out, init: Init::Xavier }),
+            LayerSpec::Conv(cs, _) => Some(LayerSpec::Conv(cs, Init::Xavier)),
+            LayerSpec::Gru { hidden, .. } => Some(LayerSpec::Gru { hidden, init: Init::Xavier }),
+            LayerSpec::Act(a) => Some(LayerSpec::Act(a)),
+            LayerSpec::LayerNorm => Some(LayerSpec::LayerNorm),
+            LayerSpec::Dropout(_) | LayerSpec::Flatten | LayerSpec::Output(_) => None,
+        }).collect()
+    }
+}
+
+impl std::fmt::Display for LayerSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let suffix = |init: &Init| if *init == Init::Xavier { String::new() } else { format!("@{}", init.name()) };
+        match self {
+            LayerSpec::Linear { out, init } => write!(f, "{out}{}", suffix(init)),
+            LayerSpec::Conv(c, init) => write!(f, "conv:{}:{}:{}:{}{}", c.out_ch, c.kernel, c.stride, c.padding, suffix(init)),
+            LayerSpec::Gru { hidden, init } => write!(f, "gru:{hidden}{}", suffix(init)),
+            LayerSpec::Act(a) => write!(f, "{}", a.name()),
+            LayerSpec::LayerNorm => write!(f, "ln"),
+            LayerSpec::Dropout(rate) => write!(f, "drop:{rate}"),
+            LayerSpec::Flatten => write!(f, "flatten"),
+            LayerSpec::Output(init) => write!(f, "out@{}", init.name()),
+        }
+    }
+}
+
+/// A built layer of `Net`.
+#[derive(Clone)]
+enum Layer {
+    Linear(Linear),
+    Conv(Conv2d),
+    Gru(Box<Gru>),
+    Norm(LayerNorm),
+    Dropout(Dropout),
+    Act(Act),
+    Flatten(Flatten),
+}
+
+impl Layer {
+    // `train` switches on the stochastic layers (dropout).
+    fn forward(&mut self, x: Vec<f32>, train: bool) -> Vec<f32> {
+        match self {
+            Layer::Linear(l) => l.forward(&x),
+            Layer::Conv(c) => c.forward(&x),
+            Layer::Gru(g) => g.forward(&x),
+            Layer::Norm(n) => n.forward(&x),
+            Layer::Dropout(d) => d.forward(x, train),
+            Layer::Act(a) => a.forward(x),
+            Layer::Flatten(f) => f.forward(&x),
+        }
+    }
+
+    fn backward(&mut self, d: Vec<f32>) -> Vec<f32> {
+        match self {
+            Layer::Linear(l) => l.backward(&d),
+            Layer::Conv(c) => c.backward(&d),
+            Layer::Gru(g) => g.backward(&d),
+            Layer::Norm(n) => n.backward(&d),
+            Layer::Dropout(dr) => dr.backward(d),
+            Layer::Act(a) => a.backward(d),
+            Layer::Flatten(f) => f.backward(d),
+        }
+    }
+
+    // Trainable tensors (weights/gain, bias); empty for parameter-free layers.
+    fn params(&self) -> Vec<&Vec<f32>> {
+        match self {
+            Layer::Linear(l) => vec![&l.w, &l.b],
+            Layer::Conv(c) => vec![&c.w, &c.b],
+            Layer::Gru(g) => vec![&g.wx, &g.wh, &g.bx, &g.bh],
+            Layer::Norm(n) => vec![&n.g, &n.b],
+            _ => Vec::new(),
+        }
+    }
+
+    fn params_mut(&mut self) -> Vec<&mut Vec<f32>> {
+        match self {
+            Layer::Linear(l) => vec![&mut l.w, &mut l.b],
+            Layer::Conv(c) => vec![&mut c.w, &mut c.b],
+            Layer::Gru(g) => vec![&mut g.wx, &mut g.wh, &mut g.bx, &mut g.bh],
+            Layer::Norm(n) => vec![&mut n.g, &mut n.b],
+            _ => Vec::new(),
+        }
+    }
+
+    fn zero_grad(&mut self) {
+        match self {
+            Layer::Linear(l) => l.zero_grad(),
+            Layer::Conv(c) => c.zero_grad(),
+            Layer::Gru(g) => g.zero_grad(),
+            Layer::Norm(n) => n.zero_grad(),
+            _ => {}
+        }
+    }
+
+    fn grad_l2_sum(&self) -> f32 {
+        match self {
+            Layer::Linear(l) => l.grad_l2_sum(),
+            Layer::Conv(c) => c.grad_l2_sum(),
+            Layer::Gru(g) => g.grad_l2_sum(),
+            Layer::Norm(n) => n.grad_l2_sum(),
+            _ => 0.0,
+        }
+    }
+
+    fn non_finite_in_params_or_grads(&self) -> bool {
+        match self {
+            Layer::Linear(l) => l.non_finite_in_params_or_grads(),
+            Layer::Conv(c) => c.non_finite_in_params_or_grads(),
+            Layer::Gru(g) => g.non_finite_in_params_or_grads(),
+            Layer::Norm(n) => n.non_finite_in_params_or_grads(),
+            _ => false,
+        }
+    }
+
+    // Trainable tensors with their gradients for the optimizer; weight matrices decay,
+    // biases and layer-norm gains do not.
+    fn param_grads(&mut self) -> Vec<Param<'_>> {
+        fn p<'a>(value: &'a mut [f32], grad: &'a [f32], decay: bool) -> Param<'a> { Param { value, grad, decay } }
+        match self {
+            Layer::Linear(l) => vec![p(&mut l.w, &l.gw, true), p(&mut l.b, &l.gb, false)],
+            Layer::Conv(c) => vec![p(&mut c.w, &c.gw, true), p(&mut c.b, &c.gb, false)],
+            Layer::Gru(g) => vec![
+                p(&mut g.wx, &g.gwx, true), p(&mut g.wh, &g.gwh, true),
+                p(&mut g.bx, &g.gbx, false), p(&mut g.bh, &g.gbh, false),
+            ],
+            Layer::Norm(n) => vec![p(&mut n.g, &n.gg, false), p(&mut n.b, &n.gb, false)],
+            _ => Vec::new(),
+        }
+    }
+
+    // Clamp parameters into [-max_abs, max_abs]; returns how many were out of range.
+    fn clamp_params(&mut self, max_abs: f32) -> usize {
+        let mut n = 0;
+        for v in self.params_mut().into_iter().flatten() {
+            if v.abs() > max_abs {
+                *v = v.clamp(-max_abs, max_abs);
+                n += 1;
             }
         }
+        n
     }
-    fn backward(&self, da: &mut [f32]) {
-        for i in 0..da.len() {
-            if self.mask[i] == 0 { da[i] = 0.0; }
+
+    // Parameters in `params` order (a linear layer first records its in/out sizes).
+    fn write_to(&self, out: &mut Vec<u8>) {
+        if let Layer::Linear(l) = self {
+            out.extend_from_slice(&(l.in_dim as u32).to_le_bytes());
+            out.extend_from_slice(&(l.out_dim as u32).to_le_bytes());
+        }
+        for v in self.params().into_iter().flatten() {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    // Inverse of `write_to`: appends the layer's parameters to `out`. v1 files also stored
+    // two Adam moments per parameter after the parameters; `moments` skips them.
+    fn read_from(&self, rd: &mut ByteReader, moments: bool, out: &mut Vec<f32>) -> std::io::Result<()> {
+        if let Layer::Linear(l) = self {
+            let (i, o) = (rd.u32()?, rd.u32()?);
+            if (i, o) != (l.in_dim, l.out_dim) {
+                return Err(std::io::Error::other(format!(
+                    "linear layer {i}->{o} in the file, {}->{} in the net", l.in_dim, l.out_dim,
+                )));
+            }
+        }
+        let n: usize = self.params().iter().map(|p| p.len()).sum();
+        out.extend(rd.f32s(n)?);
+        if moments { rd.bytes(2 * 4 * n)?; }
+        Ok(())
+    }
+
+    // Everything backward needs from the last forward pass, so a sequence of steps can be
+    // replayed backwards (BPTT).
+    fn cache(&self) -> Vec<f32> {
+        match self {
+            Layer::Linear(l) => l.last_x.clone(),
+            Layer::Conv(c) => c.last_x.clone(),
+            Layer::Gru(g) => g.cache(),
+            Layer::Norm(n) => n.last_xhat.iter().copied().chain([n.last_inv_std]).collect(),
+            Layer::Dropout(d) => d.mask.clone(),
+            Layer::Act(a) => a.last_x.clone(),
+            Layer::Flatten(_) => Vec::new(),
+        }
+    }
+
+    fn restore(&mut self, mut c: Vec<f32>) {
+        match self {
+            Layer::Linear(l) => l.last_x = c,
+            Layer::Conv(cv) => cv.last_x = c,
+            Layer::Gru(g) => g.restore(&c),
+            Layer::Norm(n) => {
+                n.last_inv_std = c.pop().unwrap_or(0.0);
+                n.last_xhat = c;
+            }
+            Layer::Dropout(d) => d.mask = c,
+            Layer::Act(a) => a.last_x = c,
+            Layer::Flatten(_) => {}
         }
     }
 }
 
-/// Net: [obs] -> Linear -> ReLU -> Linear -> ReLU -> Linear -> [Q]
+/// Sequential net: [obs] -> layers of the spec -> Linear -> [Q].
+/// The spec is any mix of conv blocks, linear layers, activations and flattens, see
+/// `LayerSpec::parse_list`; `LayerSpec::mlp` is the classic two-hidden-layer head.
+#[derive(Clone)]
 pub struct Net {
     pub din: usize,
-    pub h1: usize,
-    pub h2: usize,
     pub dout: usize,
 
-    l1: Linear, a1: ReLU,
-    l2: Linear, a2: ReLU,
-    l3: Linear,
-
-    pub t_adam: u64,
+    in_shape: (usize, usize, usize), // (c, h, w) of the input; (din, 1, 1) for flat features
+    arch: Vec<LayerSpec>,            // hidden part of the spec (without the output layer)
+    layers: Vec<Layer>,              // built layers, output Linear last
+    seq_caches: Vec<Vec<Vec<f32>>>,  // per step, per layer caches of `forward_seq_train`
 }
 
 impl Net {
-    pub fn new(din: usize, h1: usize, h2: usize, dout: usize, mut rng: LcgRng) -> Self {
-        let l1 = Linear::new(din, h1, &mut rng);
-        let l2 = Linear::new(h1, h2, &mut rng);
-        let l3 = Linear::new(h2, dout, &mut rng);
-        Self { din, h1, h2, dout, l1, a1: ReLU::new(h1), l2, a2: ReLU::new(h2), l3, t_adam: 0 }
+    /// Build the layers of `arch` over a `(c, h, w)` input, then the output layer.
+    /// The spec must pass `check_spec` for this input.
+    pub fn from_spec(in_shape: (usize, usize, usize), arch: &[LayerSpec], dout: usize, mut rng: LcgRng) -> Self {
+        let (mut c, mut h, mut w) = in_shape;
+        let mut layers = Vec::with_capacity(arch.len() + 1);
+        let out_init = match arch.last() {
+            Some(LayerSpec::Output(init)) => *init,
+            _ => Init::Xavier,
+        };
+        for &spec in arch.iter().chain(std::iter::once(&LayerSpec::Linear { out: dout, init: out_init })) {
+            match spec {
+                LayerSpec::Linear { out, init } => {
+                    layers.push(Layer::Linear(Linear::new(c * h * w, out, init, &mut rng)));
+                    (c, h, w) = (out, 1, 1);
+                }
+                LayerSpec::Conv(cs, init) => {
+                    let conv = Conv2d::new(c, h, w, cs, init, &mut rng);
+                    (c, h, w) = (cs.out_ch, conv.out_h, conv.out_w);
+                    layers.push(Layer::Conv(conv));
+                }
+                LayerSpec::Act(kind) => layers.push(Layer::Act(Act { kind, last_x: Vec::new() })),
+                LayerSpec::Gru { hidden, init } => {
+                    layers.push(Layer::Gru(Box::new(Gru::new(c * h * w, hidden, init, &mut rng))));
+                    (c, h, w) = (hidden, 1, 1);
+                }
+                LayerSpec::LayerNorm => layers.push(Layer::Norm(LayerNorm::new(c * h * w))),
+                LayerSpec::Dropout(rate) => {
+                    let seed = mix_seed(rng.gen_range_u32(u32::MAX) as u64 ^ layers.len() as u64);
+                    layers.push(Layer::Dropout(Dropout::new(rate, LcgRng::new(seed))));
+                }
+                LayerSpec::Flatten => {
+                    layers.push(Layer::Flatten(Flatten { len: c * h * w }));
+                    (c, h, w) = (c * h * w, 1, 1);
+                }
+                LayerSpec::Output(_) => {} // built as the last Linear above
+            }
+        }
+        Self {
+            din: in_shape.0 * in_shape.1 * in_shape.2,
+            dout,
+            in_shape,
+            arch: arch.to_vec(),
+            layers,
+            seq_caches: Vec::new(),
+        }
+    }
+
+    /// Check that every conv block of `arch` fits the feature map it receives.
+    pub fn check_spec(in_shape: (usize, usize, usize), arch: &[LayerSpec]) -> Result<(), String> {
+        let (mut c, mut h, mut w) = in_shape;
+        for spec in arch {
+            match *spec {
+                LayerSpec::Linear { out, .. } => (c, h, w) = (out, 1, 1),
+                LayerSpec::Gru { hidden, .. } => (c, h, w) = (hidden, 1, 1),
+                LayerSpec::Conv(cs, _) => {
+                    if h + 2 * cs.padding < cs.kernel || w + 2 * cs.padding < cs.kernel {
+                        return Err(format!("{spec}: kernel does not fit the {c}x{h}x{w} input"));
+                    }
+                    let oh = (h + 2 * cs.padding - cs.kernel) / cs.stride + 1;
+                    let ow = (w + 2 * cs.padding - cs.kernel) / cs.stride + 1;
+                    (c, h, w) = (cs.out_ch, oh, ow);
+                }
+                LayerSpec::Act(_) | LayerSpec::LayerNorm | LayerSpec::Dropout(_) | LayerSpec::Output(_) => {}
+                LayerSpec::Flatten => (c, h, w) = (c * h * w, 1, 1),
+            }
+        }
+        Ok(())
+    }
+
+    /// Architecture for logs, e.g. `4x9x9 -> conv:16:3:1:1,relu,flatten,64,relu -> 3 (N params)`.
+    pub fn describe(&self) -> String {
+        let (c, h, w) = self.in_shape;
+        format!(
+            "{c}x{h}x{w} -> {} -> {} ({} params)",
+            LayerSpec::list_to_string(&self.arch), self.dout, self.num_params(),
+        )
     }
 
     pub fn grad_l2_sum_all(&self) -> f32 {
-        self.l1.grad_l2_sum() + self.l2.grad_l2_sum() + self.l3.grad_l2_sum()
+        self.layers.iter().map(|l| l.grad_l2_sum()).sum()
     }
     pub fn non_finite_any(&self) -> bool {
-        self.l1.non_finite_in_params_or_grads() ||
-            self.l2.non_finite_in_params_or_grads() ||
-            self.l3.non_finite_in_params_or_grads()
+        self.layers.iter().any(|l| l.non_finite_in_params_or_grads())
     }
 
     pub fn zero_grad(&mut self) {
-        self.l1.zero_grad();
-        self.l2.zero_grad();
-        self.l3.zero_grad();
+        for l in &mut self.layers { l.zero_grad(); }
     }
 
+    /// Inference forward pass (dropout off). Caches are still filled, so it may be
+    /// followed by `backward_from_output_grad` when the net has no dropout.
     pub fn forward(&mut self, x: &[f32]) -> Vec<f32> {
-        let mut z1 = self.l1.forward(x);
-        self.a1.forward(&mut z1);
-        let mut z2 = self.l2.forward(&z1);
-        self.a2.forward(&mut z2);
-        self.l3.forward(&z2)
+        self.run(x, false)
+    }
+
+    /// Training forward pass (dropout on); use it for the pass that is backpropagated.
+    pub fn forward_train(&mut self, x: &[f32]) -> Vec<f32> {
+        self.run(x, true)
     }
 
+    fn run(&mut self, x: &[f32], train: bool) -> Vec<f32> {
+        debug_assert_eq!(x.len(), self.din);
+        let mut feat = x.to_vec();
+        for l in &mut self.layers {
+            feat = l.forward(feat, train);
+        }
+        feat
+    }
+
+    /// Backward for the last forward pass. A GRU is cut off here: no gradient flows into
+    /// earlier steps (see `backward_seq`).
     pub fn backward_from_output_grad(&mut self, d_q: Vec<f32>) {
-        let mut da2 = self.l3.backward(&d_q);
-        self.a2.backward(&mut da2);
-        let mut da1 = self.l2.backward(&da2);
-        self.a1.backward(&mut da1);
-        let _dx = self.l1.backward(&da1);
+        self.clear_carry();
+        self.backward_step(d_q);
+    }
+
+    fn backward_step(&mut self, d_q: Vec<f32>) {
+        let mut d = d_q;
+        for l in self.layers.iter_mut().rev() {
+            d = l.backward(d);
+        }
+    }
+
+    fn clear_carry(&mut self) {
+        for l in &mut self.layers {
+            if let Layer::Gru(g) = l {
+                for v in &mut g.carry { *v = 0.0; }
+            }
+        }
+    }
+
+    /// True if the net has a recurrent layer (outputs depend on the hidden state).
+    pub fn is_recurrent(&self) -> bool {
+        self.layers.iter().any(|l| matches!(l, Layer::Gru(_)))
+    }
+
+    /// Zero the hidden state (start of an episode or sequence).
+    pub fn reset_state(&mut self) {
+        for l in &mut self.layers {
+            if let Layer::Gru(g) = l { g.reset_state(); }
+        }
+    }
+
+    /// Hidden state of every recurrent layer, e.g. to keep an acting state aside while the
+    /// same net trains on replayed sequences.
+    pub fn state(&self) -> Vec<Vec<f32>> {
+        self.layers.iter().filter_map(|l| match l {
+            Layer::Gru(g) => Some(g.h.clone()),
+            _ => None,
+        }).collect()
+    }
+
+    /// Inverse of `state`.
+    pub fn set_state(&mut self, state: &[Vec<f32>]) {
+        let grus = self.layers.iter_mut().filter_map(|l| match l {
+            Layer::Gru(g) => Some(g),
+            _ => None,
+        });
+        for (g, h) in grus.zip(state) {
+            g.h.clone_from(h);
+        }
+    }
+
+    /// Training forward over consecutive steps from the current hidden state; keeps the caches
+    /// of every step for `backward_seq`.
+    pub fn forward_seq_train(&mut self, xs: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        self.seq_caches.clear();
+        let mut out = Vec::with_capacity(xs.len());
+        for x in xs {
+            out.push(self.run(x, true));
+            self.seq_caches.push(self.layers.iter().map(|l| l.cache()).collect());
+        }
+        out
+    }
+
+    /// Backprop-through-time for the last `forward_seq_train`: `d_qs[t]` is dL/dQ at step t.
+    pub fn backward_seq(&mut self, d_qs: Vec<Vec<f32>>) {
+        debug_assert_eq!(d_qs.len(), self.seq_caches.len());
+        self.clear_carry();
+        let caches = std::mem::take(&mut self.seq_caches);
+        for (d, step) in d_qs.into_iter().zip(caches).rev() {
+            for (l, c) in self.layers.iter_mut().zip(step) {
+                l.restore(c);
+            }
+            self.backward_step(d);
+        }
     }
 
     /// Global grad-norm clip; return scale (<=1 if clipped).
@@ -248,60 +1176,112 @@ impl Net {
         max_norm / norm
     }
 
-    /// AdamW step for all layers.
-    pub fn step_adam(&mut self, lr: f32, b1: f32, b2: f32, eps: f32, grad_scale: f32, weight_decay: f32) {
-        self.t_adam += 1;
-        self.l1.step_adam(lr, b1, b2, eps, self.t_adam, grad_scale, weight_decay);
-        self.l2.step_adam(lr, b1, b2, eps, self.t_adam, grad_scale, weight_decay);
-        self.l3.step_adam(lr, b1, b2, eps, self.t_adam, grad_scale, weight_decay);
+    /// One optimizer step over all parameters with the accumulated gradients.
+    pub fn step(&mut self, opt: &mut dyn Optimizer, lr: f32, grad_scale: f32) {
+        let mut params: Vec<Param<'_>> = self.layers.iter_mut().flat_map(|l| l.param_grads()).collect();
+        opt.step(&mut params, lr, grad_scale);
     }
 
     /// Soft update θ_target ← (1−τ)θ_target + τ θ_online.
     pub fn soft_update_from(&mut self, online: &Net, tau: f32) {
-        fn mix(dst: &mut [f32], src_vals: &[f32], tau: f32) {
-            debug_assert_eq!(dst.len(), src_vals.len());
-            for (d, &s) in dst.iter_mut().zip(src_vals.iter()) {
-                *d = (1.0 - tau) * *d + tau * s;
+        for (dst, src) in self.layers.iter_mut().zip(&online.layers) {
+            for (d, s) in dst.params_mut().into_iter().zip(src.params()) {
+                debug_assert_eq!(d.len(), s.len());
+                for (d, &s) in d.iter_mut().zip(s.iter()) {
+                    *d = (1.0 - tau) * *d + tau * s;
+                }
             }
         }
-        mix(&mut self.l1.w, &online.l1.w, tau);
-        mix(&mut self.l1.b, &online.l1.b, tau);
-        mix(&mut self.l2.w, &online.l2.w, tau);
-        mix(&mut self.l2.b, &online.l2.b, tau);
-        mix(&mut self.l3.w, &online.l3.w, tau);
-        mix(&mut self.l3.b, &online.l3.b, tau);
     }
 
     /// Hard copy parameters (for target init).
     pub fn copy_from(&mut self, src: &Net) {
-        self.l1.w.clone_from(&src.l1.w);
-        self.l1.b.clone_from(&src.l1.b);
-        self.l2.w.clone_from(&src.l2.w);
-        self.l2.b.clone_from(&src.l2.b);
-        self.l3.w.clone_from(&src.l3.w);
-        self.l3.b.clone_from(&src.l3.b);
+        for (dst, src) in self.layers.iter_mut().zip(&src.layers) {
+            for (d, s) in dst.params_mut().into_iter().zip(src.params()) {
+                d.clone_from(s);
+            }
+        }
+    }
+
+    /// Total number of trainable parameters.
+    pub fn num_params(&self) -> usize {
+        self.layers.iter().flat_map(|l| l.params()).map(|p| p.len()).sum()
+    }
+
+    /// All parameters flattened into one vector (layer by layer: weights, then bias).
+    pub fn params_flat(&self) -> Vec<f32> {
+        let mut out = Vec::with_capacity(self.num_params());
+        for p in self.layers.iter().flat_map(|l| l.params()) {
+            out.extend_from_slice(p);
+        }
+        out
+    }
+
+    /// Inverse of `params_flat`.
+    pub fn set_params_flat(&mut self, p: &[f32]) {
+        debug_assert_eq!(p.len(), self.num_params());
+        let mut off = 0;
+        for dst in self.layers.iter_mut().flat_map(|l| l.params_mut()) {
+            let n = dst.len();
+            dst.copy_from_slice(&p[off..off + n]);
+            off += n;
+        }
     }
 
-    /// Clamp all parameters after an optimizer step.
-    pub fn clamp_params(&mut self, max_abs: f32) {
-        self.l1.clamp_params(max_abs);
-        self.l2.clamp_params(max_abs);
-        self.l3.clamp_params(max_abs);
+    /// Clamp all parameters after an optimizer step; returns the number of clamped values.
+    pub fn clamp_params(&mut self, max_abs: f32) -> usize {
+        self.layers.iter_mut().map(|l| l.clamp_params(max_abs)).sum()
     }
 
     // ---- serialization ----
+    // v1: "SNET" | 1 | din h1 h2 dout | t_adam | l1 l2 l3                       (plain MLP)
+    //     layer data is the parameters followed by their Adam moments.
+    // v2: "SNET" | 2 | din dout | c h w | n_layers | n × (tag, args)
+    //     | parameters of every layer that has them, in order
+    //     tags: 0 linear (out), 1 conv (out_ch kernel stride padding), 2 activation (code),
+    //     3 layer norm, 4 gru (hidden)
+    //     (flatten, dropout and initializers are not recorded, see `LayerSpec::structural`)
+    //     The tag set is fixed: a new layer kind or a changed layout needs a new version.
+    //     Optimizer state is checkpointed separately, see `optim`.
+    // v2 is always written; a v1 file loads into `64,relu,64,relu` and its moments are skipped.
 
     pub fn save(&self, path: &str) -> std::io::Result<()> {
         let mut buf: Vec<u8> = Vec::new();
+        let put = |buf: &mut Vec<u8>, v: usize| buf.extend_from_slice(&(v as u32).to_le_bytes());
         buf.extend_from_slice(b"SNET");
-        buf.extend_from_slice(&1u32.to_le_bytes());
-        for v in [self.din, self.h1, self.h2, self.dout] {
-            buf.extend_from_slice(&(v as u32).to_le_bytes());
-        }
-        buf.extend_from_slice(&self.t_adam.to_le_bytes());
-        self.l1.write_to(&mut buf);
-        self.l2.write_to(&mut buf);
-        self.l3.write_to(&mut buf);
+        put(&mut buf, 2);
+        put(&mut buf, self.din);
+        put(&mut buf, self.dout);
+        let (c, h, w) = self.in_shape;
+        let arch = LayerSpec::structural(&self.arch);
+        for v in [c, h, w, arch.len()] {
+            put(&mut buf, v);
+        }
+        for spec in &arch {
+            match *spec {
+                LayerSpec::Linear { out, .. } => {
+                    put(&mut buf, 0);
+                    put(&mut buf, out);
+                }
+                LayerSpec::Conv(sp, _) => {
+                    put(&mut buf, 1);
+                    for v in [sp.out_ch, sp.kernel, sp.stride, sp.padding] {
+                        put(&mut buf, v);
+                    }
+                }
+                LayerSpec::Act(a) => {
+                    put(&mut buf, 2);
+                    put(&mut buf, a.code() as usize);
+                }
+                LayerSpec::LayerNorm => put(&mut buf, 3),
+                LayerSpec::Gru { hidden, .. } => {
+                    put(&mut buf, 4);
+                    put(&mut buf, hidden);
+                }
+                LayerSpec::Dropout(_) | LayerSpec::Flatten | LayerSpec::Output(_) => {}
+            }
+        }
+        for l in &self.layers { l.write_to(&mut buf); }
         let mut f = File::create(path)?;
         f.write_all(&buf)?;
         Ok(())
@@ -311,56 +1291,222 @@ impl Net {
         let mut f = File::open(path)?;
         let mut buf = Vec::new();
         f.read_to_end(&mut buf)?;
-        if &buf[0..4] != b"SNET" { return Err(std::io::Error::new(std::io::ErrorKind::Other, "bad header")); }
-        let mut off = 4;
-        let mut rd_u32 = |o: &mut usize| -> u32 { let mut b=[0u8;4]; b.copy_from_slice(&buf[*o..*o+4]); *o+=4; u32::from_le_bytes(b) };
-        let mut rd_u64 = |o: &mut usize| -> u64 { let mut b=[0u8;8]; b.copy_from_slice(&buf[*o..*o+8]); *o+=8; u64::from_le_bytes(b) };
-        let _ver = rd_u32(&mut off);
-        let din = rd_u32(&mut off) as usize;
-        let h1  = rd_u32(&mut off) as usize;
-        let h2  = rd_u32(&mut off) as usize;
-        let dout= rd_u32(&mut off) as usize;
-        if din != self.din || h1 != self.h1 || h2 != self.h2 || dout != self.dout {
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, "shape mismatch"));
-        }
-        self.t_adam = rd_u64(&mut off);
-        off = self.l1.read_from(&buf, off);
-        off = self.l2.read_from(&buf, off);
-        let _ = self.l3.read_from(&buf, off);
+        let mut rd = ByteReader::new(&buf);
+        if rd.bytes(4)? != b"SNET" { return Err(std::io::Error::other("bad header")); }
+        let ver = rd.u32()?;
+        let din = rd.u32()?;
+        let (arch, dout, shape) = match ver {
+            1 => {
+                let h1 = rd.u32()?;
+                let h2 = rd.u32()?;
+                let dout = rd.u32()?;
+                let _t_adam = rd.u64()?;
+                (LayerSpec::mlp(h1, h2), dout, (din, 1, 1))
+            }
+            2 => {
+                let dout = rd.u32()?;
+                let shape = (rd.u32()?, rd.u32()?, rd.u32()?);
+                let n = rd.u32()?;
+                let mut arch = Vec::new();
+                for _ in 0..n {
+                    let spec = match rd.u32()? {
+                        0 => LayerSpec::Linear { out: rd.u32()?, init: Init::Xavier },
+                        1 => {
+                            let f = [rd.u32()?, rd.u32()?, rd.u32()?, rd.u32()?];
+                            LayerSpec::Conv(ConvSpec { out_ch: f[0], kernel: f[1], stride: f[2], padding: f[3] }, Init::Xavier)
+                        }
+                        2 => {
+                            let code = rd.u32()?;
+                            LayerSpec::Act(Activation::from_code(code as u32)
+                                .ok_or_else(|| std::io::Error::other(format!("unknown activation {code}")))?)
+                        }
+                        3 => LayerSpec::LayerNorm,
+                        4 => LayerSpec::Gru { hidden: rd.u32()?, init: Init::Xavier },
+                        t => return Err(std::io::Error::other(format!("unknown layer tag {t}"))),
+                    };
+                    arch.push(spec);
+                }
+                (arch, dout, shape)
+            }
+            v => return Err(std::io::Error::other(format!("unsupported weights version {v}"))),
+        };
+        if din != self.din || dout != self.dout {
+            return Err(std::io::Error::other("shape mismatch"));
+        }
+        // Without conv blocks only din matters, so the recorded input shape is not compared.
+        let has_conv = self.arch.iter().any(|s| matches!(s, LayerSpec::Conv(..)));
+        if LayerSpec::structural(&arch) != LayerSpec::structural(&self.arch) || (has_conv && shape != self.in_shape) {
+            return Err(std::io::Error::other(format!(
+                "architecture mismatch: file {}x{}x{} [{}], net {}x{}x{} [{}]",
+                shape.0, shape.1, shape.2, LayerSpec::list_to_string(&arch),
+                self.in_shape.0, self.in_shape.1, self.in_shape.2, LayerSpec::list_to_string(&self.arch),
+            )));
+        }
+        // Everything is read before anything is assigned, so a bad file leaves the net as is.
+        let mut params = Vec::with_capacity(self.num_params());
+        for l in &self.layers { l.read_from(&mut rd, ver == 1, &mut params)?; }
+        self.set_params_flat(&params);
         Ok(())
     }
+}
 
-    // --- private (serialization helpers) ---
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn write_to(&self, _out: &mut Vec<u8>) {}
-    fn read_from(&mut self, _data: &[u8], _off: usize) -> usize { _off }
-}
+    // L = <c, conv2(conv1(x))>, so dL/dy = c.
+    fn conv_loss(c1: &mut Conv2d, c2: &mut Conv2d, x: &[f32], c: &[f32]) -> f64 {
+        let y = c2.forward(&c1.forward(x));
+        y.iter().zip(c).map(|(&y, &c)| y as f64 * c as f64).sum()
+    }
 
-// keep Linear::write_to/read_from visible to Net
-impl Linear {
-    fn write_to(&self, out: &mut Vec<u8>) {
-        out.extend_from_slice(&(self.in_dim as u32).to_le_bytes());
-        out.extend_from_slice(&(self.out_dim as u32).to_le_bytes());
-        for v in &self.w { out.extend_from_slice(&v.to_le_bytes()); }
-        for v in &self.b { out.extend_from_slice(&v.to_le_bytes()); }
-        for v in &self.mw { out.extend_from_slice(&v.to_le_bytes()); }
-        for v in &self.vw { out.extend_from_slice(&v.to_le_bytes()); }
-        for v in &self.mb { out.extend_from_slice(&v.to_le_bytes()); }
-        for v in &self.vb { out.extend_from_slice(&v.to_le_bytes()); }
-    }
-    fn read_from(&mut self, data: &[u8], mut off: usize) -> usize {
-        let rd_u32 = |d: &[u8], o: &mut usize| -> u32 { let mut b=[0u8;4]; b.copy_from_slice(&d[*o..*o+4]); *o+=4; u32::from_le_bytes(b) };
-        let rd_f32 = |d: &[u8], o: &mut usize| -> f32 { let mut b=[0u8;4]; b.copy_from_slice(&d[*o..*o+4]); *o+=4; f32::from_le_bytes(b) };
-        let in_dim  = rd_u32(data, &mut off) as usize;
-        let out_dim = rd_u32(data, &mut off) as usize;
-        assert_eq!(in_dim, self.in_dim);
-        assert_eq!(out_dim, self.out_dim);
-        for v in &mut self.w  { *v = rd_f32(data, &mut off); }
-        for v in &mut self.b  { *v = rd_f32(data, &mut off); }
-        for v in &mut self.mw { *v = rd_f32(data, &mut off); }
-        for v in &mut self.vw { *v = rd_f32(data, &mut off); }
-        for v in &mut self.mb { *v = rd_f32(data, &mut off); }
-        for v in &mut self.vb { *v = rd_f32(data, &mut off); }
-        off
+    // Compare backward gradients with central differences. `nudged(k, i, d)` adds `d` to value `i`
+    // of the variable behind `analytic[k]` and returns the loss.
+    fn check_grads(name: &str, analytic: &[Vec<f32>], mut nudged: impl FnMut(usize, usize, f32) -> f64) {
+        let h = 1e-2;
+        for (k, grads) in analytic.iter().enumerate() {
+            for (i, &a) in grads.iter().enumerate() {
+                let up = nudged(k, i, h);
+                let down = nudged(k, i, -2.0 * h);
+                nudged(k, i, h);
+                let numeric = ((up - down) / (2.0 * h as f64)) as f32;
+                assert!(
+                    (numeric - a).abs() <= 1e-2 + 2e-2 * numeric.abs(),
+                    "{name}: gradient {k}/{i}: numeric {numeric}, backward {a}",
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn conv_gradients() {
+        // The second conv's input gradient reaches the first conv's weights.
+        let mut rng = LcgRng::new(7);
+        let mut c1 = Conv2d::new(2, 5, 5, ConvSpec { out_ch: 2, kernel: 3, stride: 1, padding: 1 }, Init::Xavier, &mut rng);
+        let mut c2 = Conv2d::new(2, c1.out_h, c1.out_w, ConvSpec { out_ch: 2, kernel: 3, stride: 2, padding: 1 }, Init::Xavier, &mut rng);
+        let mut vec = |n: usize| (0..n).map(|_| rng.next_f32() * 2.0 - 1.0).collect::<Vec<f32>>();
+        let mut x = vec(2 * 5 * 5);
+        let c = vec(c2.out_len());
+
+        c1.zero_grad();
+        c2.zero_grad();
+        conv_loss(&mut c1, &mut c2, &x, &c);
+        let dx = c1.backward(&c2.backward(&c));
+        let analytic = [c1.gw.clone(), c1.gb.clone(), c2.gw.clone(), c2.gb.clone(), dx];
+        check_grads("conv", &analytic, |k, i, d| {
+            match k {
+                0 => c1.w[i] += d,
+                1 => c1.b[i] += d,
+                2 => c2.w[i] += d,
+                3 => c2.b[i] += d,
+                _ => x[i] += d,
+            }
+            conv_loss(&mut c1, &mut c2, &x, &c)
+        });
+    }
+
+    #[test]
+    fn layer_norm_gradients() {
+        let mut rng = LcgRng::new(7);
+        let mut vec = |n: usize| (0..n).map(|_| rng.next_f32() * 2.0 - 1.0).collect::<Vec<f32>>();
+        let mut ln = LayerNorm::new(6);
+        ln.g = vec(6);
+        ln.b = vec(6);
+        let mut x = vec(6);
+        let c = vec(6);
+        // L = <c, ln(x)>, so dL/dy = c.
+        let loss = |ln: &mut LayerNorm, x: &[f32]| -> f64 {
+            ln.forward(x).iter().zip(&c).map(|(&y, &c)| y as f64 * c as f64).sum()
+        };
+
+        ln.zero_grad();
+        loss(&mut ln, &x);
+        let dx = ln.backward(&c);
+        let analytic = [ln.gg.clone(), ln.gb.clone(), dx];
+        check_grads("layer norm", &analytic, |k, i, d| {
+            match k {
+                0 => ln.g[i] += d,
+                1 => ln.b[i] += d,
+                _ => x[i] += d,
+            }
+            loss(&mut ln, &x)
+        });
+    }
+
+    // L = Σ_t <c_t, q_t> over a sequence from a zero hidden state, so dL/dq_t = c_t.
+    fn seq_loss(net: &mut Net, xs: &[Vec<f32>], cs: &[Vec<f32>]) -> f64 {
+        net.reset_state();
+        let qs = net.forward_seq_train(xs);
+        qs.iter().zip(cs).flat_map(|(q, c)| q.iter().zip(c)).map(|(&q, &c)| q as f64 * c as f64).sum()
+    }
+
+    // Check every parameter of a net built from `spec` after `backward_seq` over `steps` inputs.
+    fn check_seq_grads(spec: &str, in_shape: (usize, usize, usize), steps: usize) {
+        let arch = LayerSpec::parse_list(spec).unwrap();
+        let mut net = Net::from_spec(in_shape, &arch, 3, LcgRng::new(7));
+        let mut rng = LcgRng::new(11);
+        let mut vec = |n: usize| (0..n).map(|_| rng.next_f32() * 2.0 - 1.0).collect::<Vec<f32>>();
+        let xs: Vec<Vec<f32>> = (0..steps).map(|_| vec(net.din)).collect();
+        let cs: Vec<Vec<f32>> = (0..steps).map(|_| vec(net.dout)).collect();
+
+        net.zero_grad();
+        seq_loss(&mut net, &xs, &cs);
+        net.backward_seq(cs.clone());
+        let mut analytic = Vec::new();
+        for l in &mut net.layers {
+            for p in l.param_grads() { analytic.extend_from_slice(p.grad); }
+        }
+
+        let mut params = net.params_flat();
+        assert_eq!(analytic.len(), params.len());
+        check_grads(spec, &[analytic], |_, i, d| {
+            params[i] += d;
+            net.set_params_flat(&params);
+            seq_loss(&mut net, &xs, &cs)
+        });
+    }
+
+    #[test]
+    fn gru_gradients_through_time() {
+        check_seq_grads("5,tanh,gru:4", (3, 1, 1), 4);
+        check_seq_grads("gru:3,ln,gru:3", (2, 1, 1), 3);
+    }
+
+    #[test]
+    fn save_and_load_every_layer_kind() {
+        let arch = LayerSpec::parse_list("conv:2:3:1:1,relu,flatten,6,ln,tanh,gru:4").unwrap();
+        let path = std::env::temp_dir().join(format!("snake_net_test_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let net = Net::from_spec((2, 4, 4), &arch, 3, LcgRng::new(7));
+        net.save(path).unwrap();
+
+        let mut other = Net::from_spec((2, 4, 4), &arch, 3, LcgRng::new(8));
+        other.load(path).unwrap();
+        let wider = LayerSpec::parse_list("conv:2:3:1:1,relu,flatten,6,ln,tanh,gru:5").unwrap();
+        let err = Net::from_spec((2, 4, 4), &wider, 3, LcgRng::new(8)).load(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(other.params_flat(), net.params_flat());
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn parse_layer_list() {
+        let arch = LayerSpec::parse_list("conv:16:3:1:1@he, relu,flatten,128@orth,ln,drop:0.1,gru:32,tanh,out@zero").unwrap();
+        assert_eq!(arch, vec![
+            LayerSpec::Conv(ConvSpec { out_ch: 16, kernel: 3, stride: 1, padding: 1 }, Init::He),
+            LayerSpec::Act(Activation::Relu),
+            LayerSpec::Flatten,
+            LayerSpec::Linear { out: 128, init: Init::Orthogonal },
+            LayerSpec::LayerNorm,
+            LayerSpec::Dropout(0.1),
+            LayerSpec::Gru { hidden: 32, init: Init::Xavier },
+            LayerSpec::Act(Activation::Tanh),
+            LayerSpec::Output(Init::Zero),
+        ]);
+        assert_eq!(LayerSpec::parse_list(&LayerSpec::list_to_string(&arch)).unwrap(), arch);
+        assert_eq!(LayerSpec::parse_list("").unwrap(), vec![]);
+        for bad in ["0", "conv:16:3:1", "conv:0:3:1:1", "gru:0", "drop:1", "relu@he", "64@zero", "out,64", "64@foo", "sigmoid"] {
+            assert!(LayerSpec::parse_list(bad).is_err(), "{bad} should be rejected");
+        }
     }
 }
@@ -0,0 +1,287 @@
+// Gradient-descent optimizers for `Net` parameters.
+//
+//   sgd[:M]                  SGD with momentum M (default 0 = plain SGD)
+//   nesterov[:M]             SGD with Nesterov momentum (default 0.9)
+//   rmsprop[:A[:E]]          centered RMSProp as in the DQN paper: running means of g and g²
+//                            with decay A (0.95), step g / sqrt(E[g²] − E[g]² + E) with E = 0.01
+//   adam[:B1[:B2[:E]]]       Adam (0.9, 0.999, 1e-8)
+//   adamw[:B1[:B2[:E[:WD]]]] Adam with decoupled weight decay WD (1e-4) on weight matrices
+//
+// Optimizer state (step count and per-tensor buffers) lives outside the layers and is
+// checkpointed on its own, see `save_state` / `load_state`.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use crate::utils::ByteReader;
+
+/// One trainable tensor with its gradient; `decay` marks weight matrices (not biases/gains).
+pub struct Param<'a> {
+    pub value: &'a mut [f32],
+    pub grad: &'a [f32],
+    pub decay: bool,
+}
+
+/// Step count plus `slots` buffers per parameter tensor (momenta, squared-gradient averages...).
+#[derive(Clone, Default)]
+pub struct OptState {
+    pub t: u64,
+    pub buffers: Vec<Vec<Vec<f32>>>, // [tensor][slot][element]
+}
+
+impl OptState {
+    // (Re)allocate zeroed buffers if they do not match the tensors, e.g. on the first step.
+    fn fit(&mut self, params: &[Param<'_>], slots: usize) {
+        let ok = self.buffers.len() == params.len()
+            && self.buffers.iter().zip(params).all(|(b, p)| b.len() == slots && b.iter().all(|s| s.len() == p.value.len()));
+        if !ok {
+            self.t = 0;
+            self.buffers = params.iter().map(|p| vec![vec![0.0; p.value.len()]; slots]).collect();
+        }
+    }
+}
+
+pub trait Optimizer {
+    /// Family name stored with the state; optimizers of one family can resume each other's state.
+    fn family(&self) -> &'static str;
+    /// One update of every tensor (always passed in the same order); gradients are multiplied
+    /// by `grad_scale` (the global-norm clip factor) first.
+    fn step(&mut self, params: &mut [Param<'_>], lr: f32, grad_scale: f32);
+    fn state(&self) -> &OptState;
+    fn state_mut(&mut self) -> &mut OptState;
+}
+
+pub struct Sgd {
+    momentum: f32,
+    nesterov: bool,
+    st: OptState,
+}
+
+impl Optimizer for Sgd {
+    fn family(&self) -> &'static str { "sgd" }
+
+    fn step(&mut self, params: &mut [Param<'_>], lr: f32, grad_scale: f32) {
+        self.st.fit(params, 1);
+        self.st.t += 1;
+        for (p, b) in params.iter_mut().zip(&mut self.st.buffers) {
+            for ((w, &g), v) in p.value.iter_mut().zip(p.grad).zip(&mut b[0]) {
+                let g = g * grad_scale;
+                *v = self.momentum * *v + g;
+                *w -= lr * if self.nesterov { g + self.momentum * *v } else { *v };
+            }
+        }
+    }
+
+    fn state(&self) -> &OptState { &self.st }
+    fn state_mut(&mut self) -> &mut OptState { &mut self.st }
+}
+
+pub struct RmsProp {
+    alpha: f32,
+    eps: f32,
+    st: OptState,
+}
+
+impl Optimizer for RmsProp {
+    fn family(&self) -> &'static str { "rmsprop" }
+
+    fn step(&mut self, params: &mut [Param<'_>], lr: f32, grad_scale: f32) {
+        self.st.fit(params, 2);
+        self.st.t += 1;
+        let a = self.alpha;
+        for (p, b) in params.iter_mut().zip(&mut self.st.buffers) {
+            let [mean, sq] = &mut b[..] else { unreachable!() };
+            for i in 0..p.value.len() {
+                let g = p.grad[i] * grad_scale;
+                mean[i] = a * mean[i] + (1.0 - a) * g;
+                sq[i] = a * sq[i] + (1.0 - a) * g * g;
+                let var = (sq[i] - mean[i] * mean[i]).max(0.0);
+                p.value[i] -= lr * g / (var + self.eps).sqrt();
+            }
+        }
+    }
+
+    fn state(&self) -> &OptState { &self.st }
+    fn state_mut(&mut self) -> &mut OptState { &mut self.st }
+}
+
+/// Adam; with `weight_decay` > 0 it is AdamW (decoupled decay on `decay` tensors).
+pub struct Adam {
+    b1: f32,
+    b2: f32,
+    eps: f32,
+    weight_decay: f32,
+    st: OptState,
+}
+
+impl Optimizer for Adam {
+    fn family(&self) -> &'static str { "adam" }
+
+    fn step(&mut self, params: &mut [Param<'_>], lr: f32, grad_scale: f32) {
+        self.st.fit(params, 2);
+        self.st.t += 1;
+        let t = self.st.t as f32;
+        let (b1, b2) = (self.b1, self.b2);
+        let corr1 = (1.0 - b1.powf(t)).max(1e-8);
+        let corr2 = (1.0 - b2.powf(t)).max(1e-8);
+        for (p, b) in params.iter_mut().zip(&mut self.st.buffers) {
+            let [m, v] = &mut b[..] else { unreachable!() };
+            let wd = if p.decay { self.weight_decay } else { 0.0 };
+            for i in 0..p.value.len() {
+                let g = p.grad[i] * grad_scale;
+                m[i] = b1 * m[i] + (1.0 - b1) * g;
+                v[i] = b2 * v[i] + (1.0 - b2) * (g * g);
+                p.value[i] -= lr * (m[i] / corr1) / ((v[i] / corr2).sqrt() + self.eps);
+                if wd > 0.0 {
+                    p.value[i] -= lr * wd * p.value[i];
+                }
+            }
+        }
+    }
+
+    fn state(&self) -> &OptState { &self.st }
+    fn state_mut(&mut self) -> &mut OptState { &mut self.st }
+}
+
+/// Optimizer choice with its hyperparameters (the learning rate comes from its schedule).
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptimSpec {
+    Sgd { momentum: f32, nesterov: bool },
+    RmsProp { alpha: f32, eps: f32 },
+    Adam { b1: f32, b2: f32, eps: f32 },
+    AdamW { b1: f32, b2: f32, eps: f32, weight_decay: f32 },
+}
+
+impl Default for OptimSpec {
+    /// AdamW(0.9, 0.999, 1e-8, decay 1e-4), the long-standing default.
+    fn default() -> Self { OptimSpec::AdamW { b1: 0.9, b2: 0.999, eps: 1e-8, weight_decay: 1e-4 } }
+}
+
+impl OptimSpec {
+    /// Parse an optimizer spec (see the module comment); omitted arguments take the defaults.
+    pub fn parse(spec: &str) -> Result<OptimSpec, String> {
+        let mut parts = spec.split(':');
+        let name = parts.next().unwrap_or_default();
+        let args = parts
+            .map(|a| a.parse::<f32>().map_err(|_| format!("bad number '{a}' in optimizer '{spec}'")))
+            .collect::<Result<Vec<f32>, String>>()?;
+        let defaults: &[f32] = match name {
+            "sgd" => &[0.0],
+            "nesterov" => &[0.9],
+            "rmsprop" => &[0.95, 0.01],
+            "adam" => &[0.9, 0.999, 1e-8],
+            "adamw" => &[0.9, 0.999, 1e-8, 1e-4],
+            _ => return Err(format!("unknown optimizer '{name}' (expected sgd, nesterov, rmsprop, adam or adamw)")),
+        };
+        if args.len() > defaults.len() {
+            return Err(format!("optimizer '{spec}' takes at most {} arguments", defaults.len()));
+        }
+        let a = |i: usize| args.get(i).copied().unwrap_or(defaults[i]);
+        Ok(match name {
+            "sgd" => OptimSpec::Sgd { momentum: a(0), nesterov: false },
+            "nesterov" => OptimSpec::Sgd { momentum: a(0), nesterov: true },
+            "rmsprop" => OptimSpec::RmsProp { alpha: a(0), eps: a(1) },
+            "adam" => OptimSpec::Adam { b1: a(0), b2: a(1), eps: a(2) },
+            _ => OptimSpec::AdamW { b1: a(0), b2: a(1), eps: a(2), weight_decay: a(3) },
+        })
+    }
+
+    /// Fresh optimizer with empty state.
+    pub fn build(&self) -> Box<dyn Optimizer> {
+        let st = OptState::default();
+        match *self {
+            OptimSpec::Sgd { momentum, nesterov } => Box::new(Sgd { momentum, nesterov, st }),
+            OptimSpec::RmsProp { alpha, eps } => Box::new(RmsProp { alpha, eps, st }),
+            OptimSpec::Adam { b1, b2, eps } => Box::new(Adam { b1, b2, eps, weight_decay: 0.0, st }),
+            OptimSpec::AdamW { b1, b2, eps, weight_decay } => Box::new(Adam { b1, b2, eps, weight_decay, st }),
+        }
+    }
+}
+
+/// Inverse of `parse`.
+impl fmt::Display for OptimSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            OptimSpec::Sgd { momentum, nesterov } => write!(f, "{}:{momentum}", if nesterov { "nesterov" } else { "sgd" }),
+            OptimSpec::RmsProp { alpha, eps } => write!(f, "rmsprop:{alpha}:{eps}"),
+            OptimSpec::Adam { b1, b2, eps } => write!(f, "adam:{b1}:{b2}:{eps}"),
+            OptimSpec::AdamW { b1, b2, eps, weight_decay } => write!(f, "adamw:{b1}:{b2}:{eps}:{weight_decay}"),
+        }
+    }
+}
+
+// State file: "SOPT" | 1 | family length | family | t | n tensors | n × (slots | slots × (len | data))
+
+/// Write the optimizer state to `path`.
+pub fn save_state(opt: &dyn Optimizer, path: &str) -> std::io::Result<()> {
+    let st = opt.state();
+    let mut buf: Vec<u8> = Vec::new();
+    let put = |buf: &mut Vec<u8>, v: usize| buf.extend_from_slice(&(v as u32).to_le_bytes());
+    buf.extend_from_slice(b"SOPT");
+    put(&mut buf, 1);
+    put(&mut buf, opt.family().len());
+    buf.extend_from_slice(opt.family().as_bytes());
+    buf.extend_from_slice(&st.t.to_le_bytes());
+    put(&mut buf, st.buffers.len());
+    for slots in &st.buffers {
+        put(&mut buf, slots.len());
+        for s in slots {
+            put(&mut buf, s.len());
+            for v in s { buf.extend_from_slice(&v.to_le_bytes()); }
+        }
+    }
+    File::create(path)?.write_all(&buf)
+}
+
+/// Read a state written by `save_state` for the same optimizer family. Buffers that do not
+/// fit the network are dropped on the next step.
+pub fn load_state(opt: &mut dyn Optimizer, path: &str) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    File::open(path)?.read_to_end(&mut buf)?;
+    let mut rd = ByteReader::new(&buf);
+    if rd.bytes(4)? != b"SOPT" { return Err(std::io::Error::other("bad header")); }
+    let ver = rd.u32()?;
+    if ver != 1 { return Err(std::io::Error::other(format!("unsupported optimizer state version {ver}"))); }
+    let n = rd.u32()?;
+    let family = String::from_utf8_lossy(rd.bytes(n)?).into_owned();
+    if family != opt.family() {
+        return Err(std::io::Error::other(format!("state belongs to the {family} optimizer, not {}", opt.family())));
+    }
+    let t = rd.u64()?;
+    let mut buffers = Vec::new();
+    for _ in 0..rd.u32()? {
+        let mut slots = Vec::new();
+        for _ in 0..rd.u32()? {
+            let len = rd.u32()?;
+            slots.push(rd.f32s(len)?.collect());
+        }
+        buffers.push(slots);
+    }
+    *opt.state_mut() = OptState { t, buffers };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_specs() {
+        assert_eq!(OptimSpec::parse("sgd").unwrap(), OptimSpec::Sgd { momentum: 0.0, nesterov: false });
+        assert_eq!(OptimSpec::parse("nesterov").unwrap(), OptimSpec::Sgd { momentum: 0.9, nesterov: true });
+        assert_eq!(OptimSpec::parse("rmsprop:0.9").unwrap(), OptimSpec::RmsProp { alpha: 0.9, eps: 0.01 });
+        assert_eq!(OptimSpec::parse("adam:0.8").unwrap(), OptimSpec::Adam { b1: 0.8, b2: 0.999, eps: 1e-8 });
+        assert_eq!(OptimSpec::parse("adamw").unwrap(), OptimSpec::default());
+        for spec in ["sgd:0.5", "nesterov:0.95", "rmsprop:0.9:0.001", "adam:0.9:0.99:0.0001", "adamw:0.9:0.999:0.00000001:0.01"] {
+            let parsed = OptimSpec::parse(spec).unwrap();
+            assert_eq!(OptimSpec::parse(&parsed.to_string()).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn reject_bad_specs() {
+        for spec in ["", "lamb", "sgd:0.9:1", "adam:x", "adamw:0.9:0.999:1e-8:0:1"] {
+            assert!(OptimSpec::parse(spec).is_err(), "{spec} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// Schedules of a scalar hyperparameter (learning rate, ε) over environment steps.
+//
+//   0.001 | const:V             constant
+//   lin:A:B:N                   linear from A to B over N steps, then B
+//   exp:A:B:N                   geometric from A to B over N steps, then B (A, B > 0)
+//   cos:A:B:W:N                 linear warmup 0 → A over W steps, then cosine A → B over N steps
+//   step:A:F:N                  A, multiplied by F every N steps
+//   pw:T0:V0,T1:V1,...          piecewise linear through (step, value) points, flat outside
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    Constant(f32),
+    Linear { start: f32, end: f32, steps: u64 },
+    Exp { start: f32, end: f32, steps: u64 },
+    Cosine { start: f32, end: f32, warmup: u64, steps: u64 },
+    Step { start: f32, factor: f32, every: u64 },
+    Piecewise(Vec<(u64, f32)>),
+}
+
+impl Schedule {
+    /// Parse a schedule spec (see the module comment).
+    pub fn parse(spec: &str) -> Result<Schedule, String> {
+        if let Ok(v) = spec.parse::<f32>() {
+            return Ok(Schedule::Constant(v));
+        }
+        let (name, rest) = spec.split_once(':').ok_or_else(|| format!("bad schedule '{spec}'"))?;
+        if name == "pw" {
+            return parse_points(spec, rest);
+        }
+        let args: Vec<&str> = rest.split(':').collect();
+        let f = |i: usize| -> Result<f32, String> {
+            args[i].parse().map_err(|_| format!("bad number '{}' in schedule '{spec}'", args[i]))
+        };
+        let n = |i: usize| -> Result<u64, String> {
+            args[i].parse().map_err(|_| format!("bad step count '{}' in schedule '{spec}'", args[i]))
+        };
+        let want = match name {
+            "const" => 1,
+            "lin" | "exp" | "step" => 3,
+            "cos" => 4,
+            _ => return Err(format!("unknown schedule '{name}' (expected const, lin, exp, cos, step or pw)")),
+        };
+        if args.len() != want {
+            return Err(format!("schedule '{spec}' needs {want} arguments"));
+        }
+        let s = match name {
+            "const" => Schedule::Constant(f(0)?),
+            "lin" => Schedule::Linear { start: f(0)?, end: f(1)?, steps: n(2)? },
+            "exp" => {
+                let (start, end) = (f(0)?, f(1)?);
+                if start <= 0.0 || end <= 0.0 {
+                    return Err(format!("exp schedule '{spec}' needs positive values"));
+                }
+                Schedule::Exp { start, end, steps: n(2)? }
+            }
+            "cos" => Schedule::Cosine { start: f(0)?, end: f(1)?, warmup: n(2)?, steps: n(3)? },
+            _ => {
+                let every = n(2)?;
+                if every == 0 {
+                    return Err(format!("step schedule '{spec}' needs a nonzero period"));
+                }
+                Schedule::Step { start: f(0)?, factor: f(1)?, every }
+            }
+        };
+        Ok(s)
+    }
+
+    /// Value after `t` steps.
+    pub fn value(&self, t: u64) -> f32 {
+        match *self {
+            Schedule::Constant(v) => v,
+            Schedule::Linear { start, end, steps } => start + progress(t, steps) * (end - start),
+            Schedule::Exp { start, end, steps } => start * (end / start).powf(progress(t, steps)),
+            Schedule::Cosine { start, end, warmup, steps } => {
+                if t < warmup {
+                    return start * t as f32 / warmup as f32;
+                }
+                let p = progress(t - warmup, steps);
+                end + 0.5 * (start - end) * (1.0 + (std::f32::consts::PI * p).cos())
+            }
+            Schedule::Step { start, factor, every } => start * (factor as f64).powf((t / every) as f64) as f32,
+            Schedule::Piecewise(ref pts) => {
+                let i = pts.partition_point(|&(s, _)| s <= t);
+                if i == 0 { return pts[0].1; }
+                if i == pts.len() { return pts[i - 1].1; }
+                let ((t0, v0), (t1, v1)) = (pts[i - 1], pts[i]);
+                v0 + (t - t0) as f32 / (t1 - t0) as f32 * (v1 - v0)
+            }
+        }
+    }
+}
+
+// Fraction of `steps` done after t steps, clamped to [0, 1] (1 for a zero-length schedule).
+fn progress(t: u64, steps: u64) -> f32 {
+    if steps == 0 { 1.0 } else { (t as f64 / steps as f64).min(1.0) as f32 }
+}
+
+// `pw:T0:V0,T1:V1,...` with strictly increasing steps.
+fn parse_points(spec: &str, rest: &str) -> Result<Schedule, String> {
+    let mut pts: Vec<(u64, f32)> = Vec::new();
+    for part in rest.split(',') {
+        let (t, v) = part.split_once(':').ok_or_else(|| format!("bad point '{part}' in schedule '{spec}'"))?;
+        let t: u64 = t.parse().map_err(|_| format!("bad step '{t}' in schedule '{spec}'"))?;
+        let v: f32 = v.parse().map_err(|_| format!("bad value '{v}' in schedule '{spec}'"))?;
+        if pts.last().is_some_and(|&(prev, _)| t <= prev) {
+            return Err(format!("schedule '{spec}': steps must increase"));
+        }
+        pts.push((t, v));
+    }
+    Ok(Schedule::Piecewise(pts))
+}
+
+/// Inverse of `parse`.
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Constant(v) => write!(f, "{v}"),
+            Schedule::Linear { start, end, steps } => write!(f, "lin:{start}:{end}:{steps}"),
+            Schedule::Exp { start, end, steps } => write!(f, "exp:{start}:{end}:{steps}"),
+            Schedule::Cosine { start, end, warmup, steps } => write!(f, "cos:{start}:{end}:{warmup}:{steps}"),
+            Schedule::Step { start, factor, every } => write!(f, "step:{start}:{factor}:{every}"),
+            Schedule::Piecewise(pts) => {
+                let parts: Vec<String> = pts.iter().map(|(t, v)| format!("{t}:{v}")).collect();
+                write!(f, "pw:{}", parts.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_specs() {
+        assert_eq!(Schedule::parse("0.001").unwrap(), Schedule::Constant(0.001));
+        assert_eq!(Schedule::parse("const:0.5").unwrap(), Schedule::Constant(0.5));
+        assert_eq!(Schedule::parse("lin:1:0.1:100").unwrap(), Schedule::Linear { start: 1.0, end: 0.1, steps: 100 });
+        assert_eq!(Schedule::parse("exp:1:0.01:50").unwrap(), Schedule::Exp { start: 1.0, end: 0.01, steps: 50 });
+        assert_eq!(
+            Schedule::parse("cos:0.001:0:10:90").unwrap(),
+            Schedule::Cosine { start: 0.001, end: 0.0, warmup: 10, steps: 90 },
+        );
+        assert_eq!(Schedule::parse("step:1:0.5:10").unwrap(), Schedule::Step { start: 1.0, factor: 0.5, every: 10 });
+        assert_eq!(Schedule::parse("pw:0:1,100:0.5,200:0").unwrap(), Schedule::Piecewise(vec![(0, 1.0), (100, 0.5), (200, 0.0)]));
+        for spec in ["lin:1:0.1:100", "cos:0.001:0:10:90", "step:1:0.5:10", "pw:0:1,100:0.5"] {
+            assert_eq!(Schedule::parse(spec).unwrap().to_string(), spec);
+        }
+    }
+
+    #[test]
+    fn reject_bad_specs() {
+        for spec in ["", "lin:1:2", "lin:1:2:3:4", "lin:a:2:3", "lin:1:2:-3", "exp:0:1:10", "step:1:0.5:0",
+                     "pw:10:1,5:0", "pw:1", "sin:1:2:3"] {
+            assert!(Schedule::parse(spec).is_err(), "{spec} should be rejected");
+        }
+    }
+
+    #[test]
+    fn values() {
+        let lin = Schedule::parse("lin:1:0:100").unwrap();
+        assert_eq!((lin.value(0), lin.value(50), lin.value(1000)), (1.0, 0.5, 0.0));
+        let cos = Schedule::parse("cos:1:0:10:100").unwrap();
+        assert_eq!((cos.value(5), cos.value(10), cos.value(110)), (0.5, 1.0, 0.0));
+        assert_eq!(Schedule::parse("step:1:0.5:10").unwrap().value(25), 0.25);
+        let pw = Schedule::parse("pw:10:1,20:0").unwrap();
+        assert_eq!((pw.value(0), pw.value(15), pw.value(30)), (1.0, 0.5, 0.0));
+    }
+}
@@ -12,8 +12,16 @@ impl Dir {
         )
     }
 
+    //direction after a left / right turn (screen coords, y grows down)
+    pub fn turn_left(self) -> Dir {
+        match self { Dir::Up => Dir::Left, Dir::Left => Dir::Down, Dir::Down => Dir::Right, Dir::Right => Dir::Up }
+    }
+    pub fn turn_right(self) -> Dir {
+        match self { Dir::Up => Dir::Right, Dir::Right => Dir::Down, Dir::Down => Dir::Left, Dir::Left => Dir::Up }
+    }
+
     //offset in direction
-    fn delta(self) -> (i32, i32) {
+    pub fn delta(self) -> (i32, i32) {
         match self {
             Dir::Up => (0, -1),
             Dir::Down => (0, 1),
@@ -21,8 +29,19 @@ impl Dir {
             Dir::Right => (1, 0),
         }
     }
+
+    //rotate an offset from the head's local frame (forward = +y) into board coords
+    pub fn rotate(self, dx: i32, dy: i32) -> (i32, i32) {
+        match self {
+            Dir::Up => (dx, -dy),
+            Dir::Down => (-dx, dy),
+            Dir::Left => (-dy, -dx),
+            Dir::Right => (dy, dx),
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct Snake {
     //segments ate stored from tail to head
     body: VecDeque<(i32, i32)>,
@@ -30,17 +49,33 @@ pub struct Snake {
     dir: Dir,
     //growth counter (how many steps to not remove the tail)
     grow: usize,
-
+    //occupancy grid (segments per cell, row-major) kept in sync with `body`,
+    //so occupies/self_collision are O(1); cells off the board are not tracked
+    w: i32,
+    h: i32,
+    grid: Vec<u8>,
+    //toroidal board: the head re-enters at the opposite edge
+    wrap: bool,
 }
 
 impl Snake {
-    //create snake of length 3 in the center, facing right
-    pub fn new(cx: i32, cy: i32) -> Self {
-        let mut body = VecDeque::new();
-        body.push_back((cx - 1, cy));
-        body.push_back((cx, cy));
-        body.push_back((cx + 1, cy));
-        Self { body, dir: Dir::Right, grow: 0 }
+    //create a horizontal snake of `len` segments facing right, head at (cx + 1, cy),
+    //on a board of w x h cells
+    pub fn new(cx: i32, cy: i32, len: usize, w: usize, h: usize) -> Self {
+        let mut s = Self {
+            body: VecDeque::new(),
+            dir: Dir::Right,
+            grow: 0,
+            w: w as i32,
+            h: h as i32,
+            grid: vec![0; w * h],
+            wrap: false,
+        };
+        let len = len.max(1) as i32;
+        for x in (cx + 2 - len)..=(cx + 1) {
+            s.push_head((x, cy));
+        }
+        s
     }
 
     //current head
@@ -68,27 +103,67 @@ impl Snake {
     pub fn advance(&mut self) {
         let (dx, dy) = self.dir.delta();
         let (hx, hy) = self.head();
-        let new_head = (hx + dx, hy + dy);
-        self.body.push_back(new_head);
+        let mut new_head = (hx + dx, hy + dy);
+        if self.wrap {
+            new_head = (new_head.0.rem_euclid(self.w), new_head.1.rem_euclid(self.h));
+        }
+        self.push_head(new_head);
         if self.grow > 0 {
             self.grow -= 1;
-        } else {
-            self.body.pop_front();
+        } else if let Some(tail) = self.body.pop_front() {
+            if let Some(i) = self.cell(tail.0, tail.1) {
+                self.grid[i] -= 1;
+            }
         }
-
     }
 
+    //enable/disable wrapping around the board edges
+    pub fn set_wrap(&mut self, wrap: bool) { self.wrap = wrap; }
+
     //mark that it needs to grow by 1 segment
     pub fn feed(&mut self) { self.grow += 1}
 
+    //lose up to n segments: pending growth is cancelled first, then the tail is cut,
+    //but the head always stays
+    pub fn shrink(&mut self, n: usize) {
+        let from_grow = n.min(self.grow);
+        self.grow -= from_grow;
+        for _ in from_grow..n {
+            if self.body.len() <= 1 {
+                break;
+            }
+            if let Some(tail) = self.body.pop_front() {
+                if let Some(i) = self.cell(tail.0, tail.1) {
+                    self.grid[i] -= 1;
+                }
+            }
+        }
+    }
+
     //check if the snake occupies cell(x,y)
     pub fn occupies(&self, x: i32, y: i32) -> bool {
-        self.body.iter().any(|&(sx, sy)| sx == x && sy == y)
+        self.cell(x, y).is_some_and(|i| self.grid[i] > 0)
     }
 
-    //head collision with body (the last segmenr is head, we dont count it)
+    //head collision with body: the head cell is counted twice
     pub fn self_collision(&self) -> bool {
-        let head = self.head();
-        self.body.iter().take(self.body.len() - 1).any(|&p| p == head)
+        let (hx, hy) = self.head();
+        self.cell(hx, hy).is_some_and(|i| self.grid[i] > 1)
+    }
+
+    //append a new head and mark its cell
+    fn push_head(&mut self, p: (i32, i32)) {
+        self.body.push_back(p);
+        if let Some(i) = self.cell(p.0, p.1) {
+            self.grid[i] += 1;
+        }
+    }
+
+    //grid index of (x,y), None off the board
+    fn cell(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.w || y >= self.h {
+            return None;
+        }
+        Some((y * self.w + x) as usize)
     }
 }
\ No newline at end of file
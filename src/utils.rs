@@ -1,4 +1,5 @@
 //simplest deterministic RNG without thrid party crates
+#[derive(Clone)]
 pub struct LcgRng { state: u64 }
 
 
@@ -19,6 +20,13 @@ impl LcgRng {
         (x as f32) / ((1u32 << 24) as f32)            // [0,1)
     }
 
+    // Нормальное N(0, 1) по Box–Muller.
+    pub fn next_gaussian(&mut self) -> f32 {
+        let u1 = self.next_f32().max(1e-7);            // Не даём log(0).
+        let u2 = self.next_f32();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+    }
+
     // Случайное число в [0, n).
     pub fn gen_range_u32(&mut self, n: u32) -> u32 {
         // Берём 32 бита и берём модуль.
@@ -26,6 +34,27 @@ impl LcgRng {
     }
 }
 
+/// Индекс максимума.
+pub fn argmax(v: &[f32]) -> usize {
+    let mut best_i = 0;                 // Текущий лучший индекс.
+    let mut best_v = v[0];              // Текущее лучшее значение.
+    for (i, &x) in v.iter().enumerate().skip(1) { // Проходим массив.
+        if x > best_v {                 // Нашли больше — обновляем.
+            best_v = x;
+            best_i = i;
+        }
+    }
+    best_i                               // Возвращаем индекс.
+}
+
+/// Перемешивание сида (финализатор SplitMix64): близкие сиды дают независимые потоки LCG.
+pub fn mix_seed(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
 pub fn has_non_finite(xs: &[f32]) -> bool {
     // Идём по всем значениям и проверяем is_finite().
     xs.iter().any(|&v| !v.is_finite())
@@ -56,4 +85,38 @@ pub fn now_millis() -> u128 {
     let now = std::time::SystemTime::now();
     let dur = now.duration_since(std::time::UNIX_EPOCH).unwrap();
     (dur.as_secs() as u128) * 1000 + (dur.subsec_nanos() as u128) / 1_000_000
-}
\ No newline at end of file
+}
+/// Чтение little-endian значений из буфера файла с проверкой границ:
+/// обрезанный файл даёт io::Error вместо паники.
+pub struct ByteReader<'a> {
+    buf: &'a [u8],
+    off: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self { Self { buf, off: 0 } }
+
+    /// Следующие `n` байт.
+    pub fn bytes(&mut self, n: usize) -> std::io::Result<&'a [u8]> {
+        let s = self.off.checked_add(n)
+            .and_then(|end| self.buf.get(self.off..end))
+            .ok_or_else(|| std::io::Error::other(format!("truncated file at byte {}", self.off)))?;
+        self.off += n;
+        Ok(s)
+    }
+
+    /// u32, сразу как usize (размеры и счётчики).
+    pub fn u32(&mut self) -> std::io::Result<usize> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()) as usize)
+    }
+
+    pub fn u64(&mut self) -> std::io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// `n` значений f32 подряд.
+    pub fn f32s(&mut self, n: usize) -> std::io::Result<impl Iterator<Item = f32> + 'a> {
+        let len = n.checked_mul(4).ok_or_else(|| std::io::Error::other("length overflow"))?;
+        Ok(self.bytes(len)?.chunks_exact(4).map(|c| f32::from_le_bytes(c.try_into().unwrap())))
+    }
+}